@@ -30,6 +30,37 @@ pub enum MolluskError<'a> {
     /// Account index exceeds maximum (255).
     #[error("    [MOLLUSK]: Account index exceeds maximum of 255: {0}")]
     AccountIndexOverflow(usize),
+    /// Address lookup table required by the message was not provided.
+    #[error("    [MOLLUSK]: Address lookup table required by the message was not provided: {0}")]
+    LookupTableMissing(&'a Pubkey),
+    /// Address lookup table index is out of bounds.
+    #[error("    [MOLLUSK]: Address lookup table index {1} is out of bounds: {0}")]
+    LookupTableIndexOutOfBounds(&'a Pubkey, u8),
+    /// Message header is inconsistent with the message's static account keys.
+    #[error("    [MOLLUSK]: Message header is invalid for {0} static account keys")]
+    InvalidMessageHeader(usize),
+    /// Compiled instruction references an account index outside the message's
+    /// account keys, including any loaded addresses.
+    #[error(
+        "    [MOLLUSK]: Instruction account index {0} is out of bounds for a message with {1} \
+         account keys"
+    )]
+    MessageAccountIndexOutOfBounds(u8, usize),
+    /// Signer seeds do not derive a valid program address.
+    #[error("    [MOLLUSK]: Signer seeds do not derive a valid program address for program: {0}")]
+    InvalidSignerSeeds(&'a Pubkey),
+    /// Derived signer address is not an account of the instruction.
+    #[error("    [MOLLUSK]: Derived signer address is not an account of the instruction: {0}")]
+    SignerNotInInstruction(&'a Pubkey),
+    /// Provided program account's owner doesn't match its cached loader.
+    #[error(
+        "    [MOLLUSK]: Program account {0} is owned by {1}, but the program is cached under \
+         loader {2}"
+    )]
+    ProgramLoaderMismatch(&'a Pubkey, &'a Pubkey, &'a Pubkey),
+    /// Sysvar account data could not be deserialized.
+    #[error("    [MOLLUSK]: Failed to deserialize sysvar account: {0}")]
+    InvalidSysvarAccount(&'a Pubkey),
 }
 
 pub trait MolluskPanic<T> {
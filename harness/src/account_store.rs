@@ -1,7 +1,7 @@
 //! A trait for implementing an account store, to be used with the
 /// `MolluskContext`.
 use {
-    solana_account::{Account, AccountSharedData},
+    solana_account::{Account, AccountSharedData, ReadableAccount},
     solana_pubkey::Pubkey,
     std::collections::HashMap,
 };
@@ -19,6 +19,46 @@ pub trait AccountStore {
 
     /// Store an account at the given public key.
     fn store_account(&mut self, pubkey: Pubkey, account: AccountSharedData);
+
+    /// Check whether an account exists at the given public key.
+    fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.get_account(pubkey).is_some()
+    }
+
+    /// Store many accounts at once.
+    fn store_accounts(&mut self, accounts: impl IntoIterator<Item = (Pubkey, AccountSharedData)>)
+    where
+        Self: Sized,
+    {
+        accounts
+            .into_iter()
+            .for_each(|(pubkey, account)| self.store_account(pubkey, account));
+    }
+
+    /// Modify the account at the given public key in place, creating it from
+    /// `default_account` if it doesn't exist.
+    ///
+    /// The default implementation gets the account, applies `f`, and stores
+    /// the result.
+    fn update_account(&mut self, pubkey: &Pubkey, f: impl FnOnce(&mut AccountSharedData))
+    where
+        Self: Sized,
+    {
+        let mut account = self
+            .get_account(pubkey)
+            .unwrap_or_else(|| self.default_account(pubkey).into());
+        f(&mut account);
+        self.store_account(*pubkey, account);
+    }
+
+    /// Get every account currently held by the store.
+    ///
+    /// Used to snapshot a store's contents, for example when forking a
+    /// `MolluskContext`. Returns `None` if the store can't enumerate its
+    /// contents, which is the default.
+    fn all_accounts(&self) -> Option<Vec<(Pubkey, AccountSharedData)>> {
+        None
+    }
 }
 
 impl AccountStore for HashMap<Pubkey, AccountSharedData> {
@@ -29,4 +69,328 @@ impl AccountStore for HashMap<Pubkey, AccountSharedData> {
     fn store_account(&mut self, pubkey: Pubkey, account: AccountSharedData) {
         self.insert(pubkey, account);
     }
+
+    fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.contains_key(pubkey)
+    }
+
+    fn store_accounts(&mut self, accounts: impl IntoIterator<Item = (Pubkey, AccountSharedData)>) {
+        self.extend(accounts);
+    }
+
+    fn update_account(&mut self, pubkey: &Pubkey, f: impl FnOnce(&mut AccountSharedData)) {
+        match self.get_mut(pubkey) {
+            Some(account) => f(account),
+            None => {
+                let mut account = self.default_account(pubkey).into();
+                f(&mut account);
+                self.insert(*pubkey, account);
+            }
+        }
+    }
+
+    fn all_accounts(&self) -> Option<Vec<(Pubkey, AccountSharedData)>> {
+        Some(
+            self.iter()
+                .map(|(pubkey, account)| (*pubkey, account.clone()))
+                .collect(),
+        )
+    }
+}
+
+/// A wrapper around any account store, which uses the provided factory to
+/// create the default account for keys the store doesn't hold.
+///
+/// For example, to have every unknown account default to a funded
+/// System-owned account:
+///
+/// ```rust,ignore
+/// let store = DefaultingAccountStore::new(HashMap::new(), |_| {
+///     Account::new(1_000_000_000, 0, &system_program::id())
+/// });
+/// ```
+pub struct DefaultingAccountStore<AS, F>
+where
+    AS: AccountStore,
+    F: Fn(&Pubkey) -> Account,
+{
+    pub store: AS,
+    pub factory: F,
+}
+
+impl<AS, F> DefaultingAccountStore<AS, F>
+where
+    AS: AccountStore,
+    F: Fn(&Pubkey) -> Account,
+{
+    /// Wrap an account store with a default account factory.
+    pub const fn new(store: AS, factory: F) -> Self {
+        Self { store, factory }
+    }
+}
+
+impl<AS, F> AccountStore for DefaultingAccountStore<AS, F>
+where
+    AS: AccountStore,
+    F: Fn(&Pubkey) -> Account,
+{
+    fn default_account(&self, pubkey: &Pubkey) -> Account {
+        (self.factory)(pubkey)
+    }
+
+    fn get_account(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+        self.store.get_account(pubkey)
+    }
+
+    fn store_account(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+        self.store.store_account(pubkey, account);
+    }
+
+    fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.store.contains(pubkey)
+    }
+
+    fn all_accounts(&self) -> Option<Vec<(Pubkey, AccountSharedData)>> {
+        self.store.all_accounts()
+    }
+}
+
+/// The fields that differ between two versions of the same account.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub lamports: bool,
+    pub data: bool,
+    pub owner: bool,
+    pub executable: bool,
+    pub rent_epoch: bool,
+}
+
+impl AccountDiff {
+    fn new(a: &impl ReadableAccount, b: &impl ReadableAccount) -> Self {
+        Self {
+            lamports: a.lamports() != b.lamports(),
+            data: a.data() != b.data(),
+            owner: a.owner() != b.owner(),
+            executable: a.executable() != b.executable(),
+            rent_epoch: a.rent_epoch() != b.rent_epoch(),
+        }
+    }
+}
+
+/// A structured report of the differences between two account stores.
+///
+/// All keys are sorted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StoreDiff {
+    /// Accounts present in the second store, but not the first.
+    pub added: Vec<Pubkey>,
+    /// Accounts present in the first store, but not the second.
+    pub removed: Vec<Pubkey>,
+    /// Accounts present in both stores, whose contents differ.
+    pub changed: Vec<(Pubkey, AccountDiff)>,
+}
+
+impl StoreDiff {
+    /// Whether the two stores were identical.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Diff two snapshots of account store contents, for example those of two
+/// `MolluskContext` runs taken with `AccountStore::all_accounts`.
+///
+/// Accepts any account type, such as `AccountSharedData` or `Account`.
+pub fn diff<T: ReadableAccount>(a: &[(Pubkey, T)], b: &[(Pubkey, T)]) -> StoreDiff {
+    let a: HashMap<&Pubkey, &T> = a
+        .iter()
+        .map(|(pubkey, account)| (pubkey, account))
+        .collect();
+    let b: HashMap<&Pubkey, &T> = b
+        .iter()
+        .map(|(pubkey, account)| (pubkey, account))
+        .collect();
+
+    let mut added: Vec<Pubkey> = b
+        .keys()
+        .filter(|key| !a.contains_key(*key))
+        .map(|key| **key)
+        .collect();
+    let mut removed: Vec<Pubkey> = a
+        .keys()
+        .filter(|key| !b.contains_key(*key))
+        .map(|key| **key)
+        .collect();
+    let mut changed: Vec<(Pubkey, AccountDiff)> = a
+        .iter()
+        .filter_map(|(key, account_a)| {
+            let account_diff = AccountDiff::new(*account_a, *b.get(key)?);
+            (account_diff != AccountDiff::default()).then_some((**key, account_diff))
+        })
+        .collect();
+
+    added.sort();
+    removed.sort();
+    changed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    StoreDiff {
+        added,
+        removed,
+        changed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_account::WritableAccount};
+
+    #[test]
+    fn test_store_accounts_and_contains() {
+        let mut store: HashMap<Pubkey, AccountSharedData> = HashMap::new();
+
+        let accounts = (0..8u64)
+            .map(|lamports| {
+                (
+                    Pubkey::new_unique(),
+                    AccountSharedData::new(lamports, 0, &Pubkey::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        accounts
+            .iter()
+            .for_each(|(pubkey, _)| assert!(!store.contains(pubkey)));
+
+        store.store_accounts(accounts.clone());
+
+        accounts.iter().for_each(|(pubkey, account)| {
+            assert!(store.contains(pubkey));
+            assert_eq!(store.get_account(pubkey).as_ref(), Some(account));
+        });
+        assert!(!store.contains(&Pubkey::new_unique()));
+    }
+
+    #[test]
+    fn test_defaulting_account_store() {
+        let store = DefaultingAccountStore::new(
+            HashMap::<Pubkey, AccountSharedData>::new(),
+            |_: &Pubkey| Account::new(1_000_000_000, 0, &solana_system_program::id()),
+        );
+        let context = crate::Mollusk::default().with_context(store);
+
+        // Neither account was ever seeded.
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instruction = solana_system_interface::instruction::transfer(&sender, &recipient, 42);
+
+        let result = context.process_instruction(&instruction);
+        assert!(result.program_result.is_ok());
+
+        let store = context.account_store.borrow();
+        assert_eq!(
+            store.get_account(&sender).unwrap().lamports(),
+            1_000_000_000 - 42
+        );
+        assert_eq!(
+            store.get_account(&recipient).unwrap().lamports(),
+            1_000_000_000 + 42
+        );
+    }
+
+    #[test]
+    fn test_update_account() {
+        let account = Pubkey::new_unique();
+        let mut store: HashMap<Pubkey, AccountSharedData> = HashMap::new();
+        store.store_account(
+            account,
+            AccountSharedData::new(1_000, 0, &solana_system_program::id()),
+        );
+        let context = crate::Mollusk::default().with_context(store);
+
+        context.update_account(&account, |account| {
+            account.checked_add_lamports(500).unwrap()
+        });
+        assert_eq!(
+            context
+                .account_store
+                .borrow()
+                .get_account(&account)
+                .unwrap()
+                .lamports(),
+            1_500
+        );
+
+        // Missing accounts start from the default account.
+        let missing = Pubkey::new_unique();
+        context.update_account(&missing, |account| account.set_lamports(42));
+        assert_eq!(
+            context
+                .account_store
+                .borrow()
+                .get_account(&missing)
+                .unwrap()
+                .lamports(),
+            42
+        );
+
+        // Wrapped stores start from their own default account.
+        let store: HashMap<Pubkey, AccountSharedData> = HashMap::new();
+        let mut store = DefaultingAccountStore::new(store, |_| {
+            Account::new(1_000, 0, &solana_system_program::id())
+        });
+        store.update_account(&missing, |account| {
+            account.checked_add_lamports(500).unwrap()
+        });
+        let account = store.get_account(&missing).unwrap();
+        assert_eq!(account.lamports(), 1_500);
+        assert_eq!(account.owner(), &solana_system_program::id());
+    }
+
+    #[test]
+    fn test_diff() {
+        let unchanged = Pubkey::new_unique();
+        let changed = Pubkey::new_unique();
+        let added = Pubkey::new_unique();
+
+        let mut store: HashMap<Pubkey, AccountSharedData> = HashMap::new();
+        store.store_accounts([
+            (unchanged, AccountSharedData::new(42, 8, &Pubkey::default())),
+            (changed, AccountSharedData::new(100, 0, &Pubkey::default())),
+        ]);
+        let baseline = store.all_accounts().unwrap();
+
+        store.store_accounts([
+            (changed, AccountSharedData::new(58, 0, &Pubkey::default())),
+            (added, AccountSharedData::new(42, 0, &Pubkey::default())),
+        ]);
+        let candidate = store.all_accounts().unwrap();
+
+        assert!(diff(&baseline, &baseline).is_empty());
+
+        let expected = StoreDiff {
+            added: vec![added],
+            removed: vec![],
+            changed: vec![(
+                changed,
+                AccountDiff {
+                    lamports: true,
+                    ..Default::default()
+                },
+            )],
+        };
+        assert_eq!(diff(&baseline, &candidate), expected);
+
+        // Owned accounts diff the same way.
+        let to_owned = |accounts: &[(Pubkey, AccountSharedData)]| {
+            accounts
+                .iter()
+                .map(|(pubkey, account)| (*pubkey, Account::from(account.clone())))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(diff(&to_owned(&baseline), &to_owned(&candidate)), expected);
+
+        let reversed = diff(&candidate, &baseline);
+        assert_eq!(reversed.added, vec![]);
+        assert_eq!(reversed.removed, vec![added]);
+    }
 }
@@ -0,0 +1,229 @@
+//! Batched instruction processing.
+//!
+//! Every call to `Mollusk::process_instruction` builds the program runtime
+//! environments and the sysvar cache from scratch. When processing thousands
+//! of instructions in a loop - such as when running fixtures or fuzzing -
+//! this setup dominates execution time.
+//!
+//! A `MolluskBatch` builds them once and reuses them for every instruction it
+//! processes. Since it borrows the `Mollusk` instance, the compute budget,
+//! feature set, and sysvars can't change for as long as the batch is alive.
+
+use {
+    crate::Mollusk,
+    mollusk_svm_result::{Check, InstructionResult},
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_instruction::Instruction,
+    solana_program_runtime::{
+        loaded_programs::ProgramRuntimeEnvironments, sysvar_cache::SysvarCache,
+    },
+    solana_pubkey::Pubkey,
+};
+
+/// A handle for processing many instructions against the same `Mollusk`
+/// instance, reusing the program runtime environments and sysvar cache
+/// between calls.
+///
+/// Results are identical to those of the corresponding `Mollusk` methods.
+pub struct MolluskBatch<'a> {
+    mollusk: &'a Mollusk,
+    program_runtime_environments: ProgramRuntimeEnvironments,
+    sysvar_cache: SysvarCache,
+}
+
+impl<'a> MolluskBatch<'a> {
+    pub(crate) fn new(mollusk: &'a Mollusk) -> Self {
+        Self {
+            mollusk,
+            program_runtime_environments: mollusk.program_runtime_environments(),
+            sysvar_cache: mollusk.sysvars.setup_sysvar_cache(&[]),
+        }
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment. Simply returns the result.
+    ///
+    /// Note: If any of the provided accounts is owned by the sysvar program,
+    /// it takes precedence over the harness's sysvars, so a fresh sysvar
+    /// cache is built for this instruction only.
+    pub fn process_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        let fallback_accounts = self.mollusk.get_account_fallbacks(
+            std::iter::once(&instruction.program_id),
+            std::iter::once(instruction),
+            accounts,
+        );
+
+        let fresh_sysvar_cache;
+        let sysvar_cache = if accounts
+            .iter()
+            .any(|(_, account)| account.owner() == &solana_sdk_ids::sysvar::id())
+        {
+            fresh_sysvar_cache = self.mollusk.sysvars.setup_sysvar_cache(accounts);
+            &fresh_sysvar_cache
+        } else {
+            &self.sysvar_cache
+        };
+
+        self.mollusk.process_instruction_inner(
+            0,
+            instruction,
+            accounts,
+            &fallback_accounts,
+            &self.mollusk.compute_budget,
+            &self.program_runtime_environments,
+            sysvar_cache,
+            None,
+            self.mollusk.config.collect_resulting_accounts,
+        )
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, then perform checks on the result. Panics if any checks
+    /// fail.
+    pub fn process_and_validate_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[Check],
+    ) -> InstructionResult {
+        let result = self.process_instruction(instruction, accounts);
+        result.run_checks_with_inputs(checks, &self.mollusk.config, self.mollusk, accounts);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_system_interface::instruction::transfer,
+        std::{
+            alloc::{GlobalAlloc, Layout, System},
+            cell::Cell,
+        },
+    };
+
+    // Counts allocations made by the current thread, so tests running in
+    // parallel don't skew each other's measurements.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn count_allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    #[test]
+    fn test_batch_matches_process_instruction() {
+        let mollusk = Mollusk::default();
+        let batch = mollusk.batch();
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let mut accounts = vec![
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        // The last transfer exceeds the sender's remaining balance.
+        for lamports in [1_000, 2_000, 3_000, 5_000] {
+            let instruction = transfer(&sender, &recipient, lamports);
+
+            let expected = mollusk.process_instruction(&instruction, &accounts);
+            let result = batch.process_instruction(&instruction, &accounts);
+
+            assert_eq!(
+                result.compute_units_consumed,
+                expected.compute_units_consumed
+            );
+            assert_eq!(result.program_result, expected.program_result);
+            assert_eq!(result.raw_result, expected.raw_result);
+            assert_eq!(result.return_data, expected.return_data);
+            assert_eq!(result.resulting_accounts, expected.resulting_accounts);
+
+            accounts = result.resulting_accounts;
+        }
+
+        assert_eq!(accounts[0].1.lamports(), 4_000);
+        assert_eq!(accounts[1].1.lamports(), 6_000);
+
+        batch.process_and_validate_instruction(
+            &transfer(&sender, &recipient, 4_000),
+            &accounts,
+            &[
+                Check::success(),
+                Check::account(&sender).lamports(0).build(),
+                Check::account(&recipient).lamports(10_000).build(),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_batch_allocates_less() {
+        let mollusk = Mollusk::default();
+        let batch = mollusk.batch();
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+        let instruction = transfer(&sender, &recipient, 1_000);
+
+        // Warm up anything built lazily on first use.
+        mollusk.process_instruction(&instruction, &accounts);
+        batch.process_instruction(&instruction, &accounts);
+
+        let unbatched = count_allocations(|| {
+            for _ in 0..100 {
+                mollusk.process_instruction(&instruction, &accounts);
+            }
+        });
+        let batched = count_allocations(|| {
+            for _ in 0..100 {
+                batch.process_instruction(&instruction, &accounts);
+            }
+        });
+
+        // Rebuilding the sysvar cache for every instruction allocates.
+        assert!(
+            batched < unbatched,
+            "batched: {batched}, unbatched: {unbatched}"
+        );
+    }
+}
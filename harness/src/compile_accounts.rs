@@ -4,8 +4,10 @@
 use {
     mollusk_svm_error::error::{MolluskError, MolluskPanic},
     solana_account::{Account, AccountSharedData, WritableAccount},
-    solana_instruction::Instruction,
-    solana_message::{LegacyMessage, Message, SanitizedMessage},
+    solana_instruction::{AccountMeta, Instruction},
+    solana_message::{
+        AddressLookupTableAccount, LegacyMessage, Message, SanitizedMessage, VersionedMessage,
+    },
     solana_pubkey::Pubkey,
     std::collections::{HashMap, HashSet},
 };
@@ -14,8 +16,9 @@ pub fn compile_accounts<'a>(
     instructions: &[Instruction],
     accounts: impl Iterator<Item = &'a (Pubkey, AccountSharedData)>,
     fallback_accounts: &HashMap<Pubkey, AccountSharedData>,
+    fee_payer: Option<&Pubkey>,
 ) -> (SanitizedMessage, Vec<(Pubkey, AccountSharedData)>) {
-    let message = Message::new(instructions, None);
+    let message = Message::new(instructions, fee_payer);
     let sanitized_message = SanitizedMessage::Legacy(LegacyMessage::new(message, &HashSet::new()));
 
     let accounts: Vec<_> = accounts.collect();
@@ -29,6 +32,97 @@ pub fn compile_accounts<'a>(
     (sanitized_message, transaction_accounts)
 }
 
+/// Decompile a versioned message into its instructions, resolving any
+/// addresses loaded from the provided lookup tables.
+pub fn decompile_versioned_message(
+    message: &VersionedMessage,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Vec<Instruction> {
+    let header = message.header();
+    let static_keys = message.static_account_keys();
+
+    let num_signers = header.num_required_signatures as usize;
+    let num_writable_signers = num_signers
+        .checked_sub(header.num_readonly_signed_accounts as usize)
+        .or_panic_with(MolluskError::InvalidMessageHeader(static_keys.len()));
+    let num_writable_unsigned = static_keys
+        .len()
+        .checked_sub(num_signers)
+        .and_then(|num_unsigned| {
+            num_unsigned.checked_sub(header.num_readonly_unsigned_accounts as usize)
+        })
+        .or_panic_with(MolluskError::InvalidMessageHeader(static_keys.len()));
+
+    // Static keys first, then all writable loaded addresses, then all
+    // readonly loaded addresses.
+    let mut account_keys: Vec<(Pubkey, bool, bool)> = static_keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let is_signer = index < num_signers;
+            let is_writable = if is_signer {
+                index < num_writable_signers
+            } else {
+                index - num_signers < num_writable_unsigned
+            };
+            (*key, is_signer, is_writable)
+        })
+        .collect();
+
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let resolve = |is_writable: bool| {
+        lookups.iter().flat_map(move |lookup| {
+            let table = lookup_tables
+                .iter()
+                .find(|table| table.key == lookup.account_key)
+                .or_panic_with(MolluskError::LookupTableMissing(&lookup.account_key));
+            let indexes = if is_writable {
+                &lookup.writable_indexes
+            } else {
+                &lookup.readonly_indexes
+            };
+            indexes.iter().map(move |index| {
+                let address = table.addresses.get(*index as usize).or_panic_with(
+                    MolluskError::LookupTableIndexOutOfBounds(&lookup.account_key, *index),
+                );
+                (*address, false, is_writable)
+            })
+        })
+    };
+    account_keys.extend(resolve(true));
+    account_keys.extend(resolve(false));
+
+    message
+        .instructions()
+        .iter()
+        .map(|compiled_ix| {
+            let key_at = |index: u8| {
+                account_keys.get(index as usize).copied().or_panic_with(
+                    MolluskError::MessageAccountIndexOutOfBounds(index, account_keys.len()),
+                )
+            };
+            let (program_id, _, _) = key_at(compiled_ix.program_id_index);
+            let accounts = compiled_ix
+                .accounts
+                .iter()
+                .map(|index| {
+                    let (pubkey, is_signer, is_writable) = key_at(*index);
+                    AccountMeta {
+                        pubkey,
+                        is_signer,
+                        is_writable,
+                    }
+                })
+                .collect();
+            Instruction {
+                program_id,
+                accounts,
+                data: compiled_ix.data.clone(),
+            }
+        })
+        .collect()
+}
+
 fn build_transaction_accounts(
     message: &SanitizedMessage,
     accounts: &[&(Pubkey, AccountSharedData)],
@@ -37,43 +131,270 @@ fn build_transaction_accounts(
 ) -> Vec<(Pubkey, AccountSharedData)> {
     let program_ids: HashSet<Pubkey> = all_instructions.iter().map(|ix| ix.program_id).collect();
 
-    message
-        .account_keys()
-        .iter()
-        .map(|key| {
-            if program_ids.contains(key) {
-                if let Some(provided_account) = accounts.iter().find(|(k, _)| k == key) {
-                    return (*key, provided_account.1.clone());
-                }
-                if let Some(fallback) = fallback_accounts.get(key) {
-                    return (*key, fallback.clone());
-                }
-                // This shouldn't happen if fallbacks are set up correctly.
-                let mut program_account = Account::default();
-                program_account.set_executable(true);
-                return (*key, program_account.into());
+    // Index the provided accounts by key, so large account sets don't require
+    // a linear search per key. If a key is provided more than once, the first
+    // occurrence wins.
+    let mut provided_accounts: HashMap<&Pubkey, &AccountSharedData> =
+        HashMap::with_capacity(accounts.len());
+    for (key, account) in accounts.iter().copied() {
+        provided_accounts.entry(key).or_insert(account);
+    }
+
+    let account_keys = message.account_keys();
+    let mut transaction_accounts = Vec::with_capacity(account_keys.len());
+
+    transaction_accounts.extend(account_keys.iter().map(|key| {
+        if program_ids.contains(key) {
+            if let Some(provided_account) = provided_accounts.get(key) {
+                return (*key, (*provided_account).clone());
             }
+            if let Some(fallback) = fallback_accounts.get(key) {
+                return (*key, fallback.clone());
+            }
+            // This shouldn't happen if fallbacks are set up correctly.
+            let mut program_account = Account::default();
+            program_account.set_executable(true);
+            return (*key, program_account.into());
+        }
 
-            if *key == solana_instructions_sysvar::ID {
-                if let Some((_, provided_account)) = accounts.iter().find(|(k, _)| k == key) {
-                    return (*key, provided_account.clone());
-                }
-                if let Some(fallback) = fallback_accounts.get(key) {
-                    return (*key, fallback.clone());
+        if *key == solana_instructions_sysvar::ID {
+            if let Some(provided_account) = provided_accounts.get(key) {
+                return (*key, (*provided_account).clone());
+            }
+            if let Some(fallback) = fallback_accounts.get(key) {
+                return (*key, fallback.clone());
+            }
+            let (_, account) = crate::instructions_sysvar::keyed_account(all_instructions.iter());
+            return (*key, account.into());
+        }
+
+        let account = provided_accounts
+            .get(key)
+            .copied()
+            .cloned()
+            .or_else(|| fallback_accounts.get(key).cloned())
+            .or_panic_with(MolluskError::AccountMissing(key));
+
+        (*key, account)
+    }));
+
+    transaction_accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_hash::Hash,
+        solana_message::{
+            compiled_instruction::CompiledInstruction,
+            v0::{self, MessageAddressTableLookup},
+            MessageHeader,
+        },
+    };
+
+    #[test]
+    fn test_compile_accounts_many() {
+        let program_id = Pubkey::new_unique();
+        let metas = (0..60)
+            .map(|i| {
+                let pubkey = Pubkey::new_unique();
+                if i % 2 == 0 {
+                    AccountMeta::new(pubkey, false)
+                } else {
+                    AccountMeta::new_readonly(pubkey, false)
                 }
-                let (_, account) =
-                    crate::instructions_sysvar::keyed_account(all_instructions.iter());
-                return (*key, account.into());
+            })
+            .collect::<Vec<_>>();
+        let instruction = Instruction::new_with_bytes(program_id, &[], metas.clone());
+
+        let accounts = metas
+            .iter()
+            .enumerate()
+            .map(|(lamports, meta)| {
+                (
+                    meta.pubkey,
+                    AccountSharedData::new(lamports as u64, 0, &Pubkey::default()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let fallback_accounts = HashMap::from([(
+            program_id,
+            AccountSharedData::from(Account {
+                executable: true,
+                ..Default::default()
+            }),
+        )]);
+
+        let (message, transaction_accounts) = compile_accounts(
+            std::slice::from_ref(&instruction),
+            accounts.iter(),
+            &fallback_accounts,
+            None,
+        );
+
+        // Every key in the message maps to its provided account, in order.
+        let account_keys = message.account_keys();
+        assert_eq!(transaction_accounts.len(), 61);
+        assert_eq!(account_keys.len(), 61);
+        for (index, (key, account)) in transaction_accounts.iter().enumerate() {
+            assert_eq!(account_keys.get(index), Some(key));
+            if let Some((_, provided)) = accounts.iter().find(|(k, _)| k == key) {
+                assert_eq!(account, provided);
+            } else {
+                assert_eq!(key, &program_id);
+                assert_eq!(account, fallback_accounts.get(&program_id).unwrap());
             }
+        }
+    }
 
-            let account = accounts
-                .iter()
-                .find(|(k, _)| k == key)
-                .map(|(_, a)| a.clone())
-                .or_else(|| fallback_accounts.get(key).cloned())
-                .or_panic_with(MolluskError::AccountMissing(key));
+    #[test]
+    fn test_compile_accounts_fee_payer() {
+        let program_id = Pubkey::new_unique();
+        let fee_payer = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new_readonly(fee_payer, false),
+            ],
+        );
 
-            (*key, account)
-        })
-        .collect()
+        let accounts = [
+            (signer, AccountSharedData::default()),
+            (fee_payer, AccountSharedData::default()),
+        ];
+        let fallback_accounts = HashMap::from([(
+            program_id,
+            AccountSharedData::from(Account {
+                executable: true,
+                ..Default::default()
+            }),
+        )]);
+
+        let (message, transaction_accounts) = compile_accounts(
+            std::slice::from_ref(&instruction),
+            accounts.iter(),
+            &fallback_accounts,
+            Some(&fee_payer),
+        );
+
+        // The fee payer is the first writable signer, even though the
+        // instruction itself only reads it.
+        assert_eq!(message.account_keys().get(0), Some(&fee_payer));
+        assert_eq!(transaction_accounts[0].0, fee_payer);
+        assert!(message.is_signer(0));
+        assert!(message.is_writable(0));
+    }
+
+    #[test]
+    fn test_decompile_versioned_message_lookup_table() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), readonly, writable],
+        };
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[1, 2, 3],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+        );
+
+        let message = VersionedMessage::V0(
+            v0::Message::try_compile(
+                &payer,
+                std::slice::from_ref(&instruction),
+                std::slice::from_ref(&lookup_table),
+                Hash::default(),
+            )
+            .unwrap(),
+        );
+        // Both accounts are loaded from the lookup table.
+        assert_eq!(message.static_account_keys(), &[payer, program_id]);
+
+        assert_eq!(
+            decompile_versioned_message(&message, std::slice::from_ref(&lookup_table)),
+            vec![instruction],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Address lookup table index 3 is out of bounds")]
+    fn test_decompile_versioned_message_lookup_index_out_of_bounds() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+
+        let message = VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![payer, program_id],
+            address_table_lookups: vec![MessageAddressTableLookup {
+                account_key: lookup_table.key,
+                writable_indexes: vec![3],
+                readonly_indexes: vec![],
+            }],
+            instructions: vec![CompiledInstruction::new_from_raw_parts(
+                1,
+                vec![],
+                vec![0, 2],
+            )],
+            ..Default::default()
+        });
+
+        decompile_versioned_message(&message, &[lookup_table]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Instruction account index 2 is out of bounds for a message with 2")]
+    fn test_decompile_versioned_message_account_index_out_of_bounds() {
+        let message = VersionedMessage::Legacy(Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 1,
+            },
+            account_keys: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            instructions: vec![CompiledInstruction::new_from_raw_parts(
+                1,
+                vec![],
+                vec![0, 2],
+            )],
+            ..Default::default()
+        });
+
+        decompile_versioned_message(&message, &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Message header is invalid for 1 static account keys")]
+    fn test_decompile_versioned_message_invalid_header() {
+        let message = VersionedMessage::Legacy(Message {
+            header: MessageHeader {
+                num_required_signatures: 2,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 0,
+            },
+            account_keys: vec![Pubkey::new_unique()],
+            ..Default::default()
+        });
+
+        decompile_versioned_message(&message, &[]);
+    }
 }
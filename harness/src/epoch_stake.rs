@@ -6,6 +6,12 @@ use {solana_pubkey::Pubkey, std::collections::HashMap};
 /// The total epoch stake is calculated by summing all vote account stakes.
 pub type EpochStake = HashMap<Pubkey, u64>;
 
+/// A map of epochs to the epoch stake during each of them.
+///
+/// Useful for modeling stake changing over time. Epochs without an entry
+/// fall back to `Mollusk::epoch_stake`.
+pub type EpochStakeByEpoch = HashMap<u64, EpochStake>;
+
 /// Create an `EpochStake` instance with a few mocked-out entries (vote accounts
 /// with stake) to achieve the provided total stake.
 pub fn create_mock_epoch_stake(target_total: u64) -> EpochStake {
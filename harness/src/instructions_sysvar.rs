@@ -5,6 +5,10 @@ use {
     solana_pubkey::Pubkey,
 };
 
+/// Create the key and account for the instructions sysvar, containing the
+/// provided instructions.
+///
+/// The current instruction index is set to `0`.
 pub fn keyed_account<'a>(instructions: impl Iterator<Item = &'a Instruction>) -> (Pubkey, Account) {
     let data = construct_instructions_data(
         instructions
@@ -36,3 +40,76 @@ pub fn keyed_account<'a>(instructions: impl Iterator<Item = &'a Instruction>) ->
         },
     )
 }
+
+/// Create the key and account for the instructions sysvar, containing the
+/// provided instructions, with the current instruction index set to
+/// `current`.
+///
+/// This is the value read on-chain by `load_current_index_checked`.
+pub fn keyed_account_with_current_index<'a>(
+    instructions: impl Iterator<Item = &'a Instruction>,
+    current: u16,
+) -> (Pubkey, Account) {
+    let (key, mut account) = keyed_account(instructions);
+    // The current index is stored in the last two bytes of the data.
+    let len = account.data.len();
+    account.data[len - 2..].copy_from_slice(&current.to_le_bytes());
+    (key, account)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_account_info::AccountInfo,
+        solana_instruction::AccountMeta,
+        solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked},
+    };
+
+    #[test]
+    fn test_keyed_account_with_current_index() {
+        let instructions = [
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]),
+            Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[2],
+                vec![AccountMeta::new(Pubkey::new_unique(), true)],
+            ),
+        ];
+
+        let (key, mut account) = keyed_account_with_current_index(instructions.iter(), 1);
+        assert_eq!(key, solana_instructions_sysvar::ID);
+
+        // Read the account the same way a program introspecting the
+        // transaction would.
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut account.lamports,
+            &mut account.data,
+            &account.owner,
+            account.executable,
+        );
+        assert_eq!(load_current_index_checked(&account_info), Ok(1));
+        for (index, instruction) in instructions.iter().enumerate() {
+            assert_eq!(
+                load_instruction_at_checked(index, &account_info).as_ref(),
+                Ok(instruction),
+            );
+        }
+
+        // The plain variant points at the first instruction.
+        let (key, mut account) = keyed_account(instructions.iter());
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut account.lamports,
+            &mut account.data,
+            &account.owner,
+            account.executable,
+        );
+        assert_eq!(load_current_index_checked(&account_info), Ok(0));
+    }
+}
@@ -129,7 +129,9 @@
 //! Note: `Mollusk::default()` will create a new `Mollusk` instance without
 //! adding any provided BPF programs. It will still contain a subset of the
 //! default builtin programs. For more builtin programs, you can add them
-//! yourself or use the `all-builtins` feature.
+//! yourself or enable them individually with the `builtin-loader-v1`,
+//! `builtin-loader-v4`, and `builtin-zk-elgamal-proof` features. The
+//! `all-builtins` feature enables all of them.
 //!
 //! ## Instruction Chains
 //!
@@ -334,9 +336,9 @@
 //! * `process_and_validate_instruction_chain`: Process and validate an
 //!   instruction chain
 //!
-//! All methods return `ContextResult` instead of `InstructionResult`, which
-//! omits the `resulting_accounts` field since accounts are managed by the
-//! context's account store.
+//! Results can be converted into a `ContextResult`, which omits the
+//! `resulting_accounts` field since accounts are managed by the context's
+//! account store.
 //!
 //! Note that `HashMap<Pubkey, Account>` implements `AccountStore` directly,
 //! so you can use it as a simple in-memory account store without needing
@@ -440,10 +442,13 @@
 //! capabilities are provided by the respective fixture crates.
 
 pub mod account_store;
+pub mod batch;
 mod compile_accounts;
 pub mod epoch_stake;
 pub mod file;
 pub mod instructions_sysvar;
+#[cfg(feature = "invocation-inspect-callback")]
+pub mod modification_tracking;
 pub mod program;
 #[cfg(feature = "register-tracing")]
 pub mod register_tracing;
@@ -453,15 +458,14 @@ pub mod sysvar;
 use crate::register_tracing::DefaultRegisterTracingCallback;
 // Re-export result module from mollusk-svm-result crate
 pub use mollusk_svm_result as result;
-#[cfg(any(feature = "fuzz", feature = "fuzz-fd"))]
-use mollusk_svm_result::Compare;
 #[cfg(feature = "precompiles")]
 use solana_precompile_error::PrecompileError;
-#[cfg(feature = "invocation-inspect-callback")]
-use solana_transaction_context::InstructionAccount;
 use {
     crate::{
-        account_store::AccountStore, epoch_stake::EpochStake, program::ProgramCache,
+        account_store::AccountStore,
+        batch::MolluskBatch,
+        epoch_stake::{EpochStake, EpochStakeByEpoch},
+        program::ProgramCache,
         sysvar::Sysvars,
     },
     agave_feature_set::FeatureSet,
@@ -470,19 +474,19 @@ use {
     },
     mollusk_svm_error::error::{MolluskError, MolluskPanic},
     mollusk_svm_result::{
-        Check, CheckContext, Config, InstructionResult,
-        types::{TransactionProgramResult, TransactionResult},
+        Check, CheckContext, Compare, Config, InstructionResult,
+        types::{ChainResult, TransactionProgramResult, TransactionResult},
     },
-    solana_account::{Account, AccountSharedData, ReadableAccount},
+    solana_account::{Account, AccountSharedData, ReadableAccount, WritableAccount},
     solana_compute_budget::compute_budget::ComputeBudget,
     solana_hash::Hash,
     solana_instruction::{AccountMeta, Instruction},
     solana_instruction_error::InstructionError,
-    solana_message::SanitizedMessage,
+    solana_message::{AddressLookupTableAccount, SanitizedMessage, VersionedMessage},
     solana_program_error::ProgramError,
     solana_program_runtime::{
         invoke_context::{EnvironmentConfig, InvokeContext},
-        loaded_programs::ProgramRuntimeEnvironments,
+        loaded_programs::{ProgramCacheEntry, ProgramCacheEntryType, ProgramRuntimeEnvironments},
         sysvar_cache::SysvarCache,
     },
     solana_pubkey::Pubkey,
@@ -490,11 +494,13 @@ use {
     solana_svm_log_collector::LogCollector,
     solana_svm_timings::ExecuteTimings,
     solana_svm_transaction::instruction::SVMInstruction,
-    solana_transaction_context::{IndexOfAccount, TransactionContext},
+    solana_transaction_context::{IndexOfAccount, InstructionAccount, TransactionContext},
     solana_transaction_error::TransactionError,
     std::{
+        borrow::Cow,
         cell::RefCell,
         collections::{HashMap, HashSet},
+        io::Write,
         iter::once,
         rc::Rc,
         sync::Arc,
@@ -516,14 +522,32 @@ pub struct Mollusk {
     pub config: Config,
     pub compute_budget: ComputeBudget,
     pub epoch_stake: EpochStake,
+    /// Epoch stake for specific epochs. If the current `Clock` epoch has an
+    /// entry, it takes precedence over `epoch_stake`.
+    pub epoch_stake_by_epoch: EpochStakeByEpoch,
     pub feature_set: FeatureSet,
+    /// The log collector, if any. Shared with any clones of this instance,
+    /// so their logs are collected here as well.
     pub logger: Option<Rc<RefCell<LogCollector>>>,
     pub program_cache: ProgramCache,
     pub sysvars: Sysvars,
+    /// The blockhash provided to the program runtime environment. See
+    /// `set_blockhash`.
+    blockhash: Hash,
+    /// The lamports per signature associated with the blockhash. See
+    /// `set_lamports_per_signature`.
+    lamports_per_signature: u64,
+    /// The maximum net growth, in bytes, of account data allowed across an
+    /// entire transaction. `None` disables the limit.
+    pub account_data_size_limit: Option<u64>,
 
     /// The callback which can be used to inspect invoke_context
     /// and extract low-level information such as bpf traces, transaction
     /// context, detailed timings, etc.
+    ///
+    /// Clones of this instance get a copy from
+    /// `InvocationInspectCallback::clone_callback`, or the empty callback if
+    /// it returns `None`.
     #[cfg(feature = "invocation-inspect-callback")]
     pub invocation_inspect_callback: Box<dyn InvocationInspectCallback>,
 
@@ -540,6 +564,21 @@ pub struct Mollusk {
     /// programs comes from the sysvars.
     #[cfg(feature = "fuzz-fd")]
     pub slot: u64,
+
+    /// The writer program logs are streamed to, if any. See
+    /// `set_log_writer`.
+    log_writer: Option<Rc<RefCell<Box<dyn Write>>>>,
+
+    /// The program runtime environments, lazily built and cached alongside
+    /// the compute budget and feature set they were built from.
+    program_runtime_environments: RefCell<Option<CachedRuntimeEnvironments>>,
+}
+
+#[derive(Clone)]
+struct CachedRuntimeEnvironments {
+    compute_budget: ComputeBudget,
+    feature_set: FeatureSet,
+    environments: ProgramRuntimeEnvironments,
 }
 
 #[cfg(feature = "invocation-inspect-callback")]
@@ -559,6 +598,15 @@ pub trait InvocationInspectCallback {
         invoke_context: &InvokeContext,
         register_tracing_enabled: bool,
     );
+
+    /// Create the callback for a clone of the `Mollusk` instance holding this
+    /// one, ie. when forking a `MolluskContext`.
+    ///
+    /// Returns `None` by default, in which case the clone starts out with the
+    /// empty callback.
+    fn clone_callback(&self) -> Option<Box<dyn InvocationInspectCallback>> {
+        None
+    }
 }
 
 #[cfg(feature = "invocation-inspect-callback")]
@@ -577,6 +625,10 @@ impl InvocationInspectCallback for EmptyInvocationInspectCallback {
     }
 
     fn after_invocation(&self, _: &Mollusk, _: &InvokeContext, _register_tracing_enabled: bool) {}
+
+    fn clone_callback(&self) -> Option<Box<dyn InvocationInspectCallback>> {
+        Some(Box::new(EmptyInvocationInspectCallback))
+    }
 }
 
 impl Default for Mollusk {
@@ -592,6 +644,45 @@ impl Default for Mollusk {
     }
 }
 
+impl Clone for Mollusk {
+    /// Note: The clone shares the original's log collector and log writer, so
+    /// logs from either instance end up in the same place. Its invocation
+    /// inspect callback comes from `InvocationInspectCallback::clone_callback`,
+    /// so callbacks such as a `ModificationTrackingCallback` keep observing
+    /// instructions processed by either instance.
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            compute_budget: self.compute_budget,
+            epoch_stake: self.epoch_stake.clone(),
+            epoch_stake_by_epoch: self.epoch_stake_by_epoch.clone(),
+            feature_set: self.feature_set.clone(),
+            logger: self.logger.clone(),
+            program_cache: self.program_cache.clone(),
+            sysvars: self.sysvars.clone(),
+            blockhash: self.blockhash,
+            lamports_per_signature: self.lamports_per_signature,
+            account_data_size_limit: self.account_data_size_limit,
+
+            #[cfg(feature = "invocation-inspect-callback")]
+            invocation_inspect_callback: self
+                .invocation_inspect_callback
+                .clone_callback()
+                .unwrap_or_else(|| Box::new(EmptyInvocationInspectCallback {})),
+
+            #[cfg(feature = "invocation-inspect-callback")]
+            enable_register_tracing: self.enable_register_tracing,
+
+            #[cfg(feature = "fuzz-fd")]
+            slot: self.slot,
+
+            log_writer: self.log_writer.clone(),
+
+            program_runtime_environments: self.program_runtime_environments.clone(),
+        }
+    }
+}
+
 impl CheckContext for Mollusk {
     fn is_rent_exempt(&self, lamports: u64, space: usize, owner: &Pubkey) -> bool {
         owner.eq(&Pubkey::default()) && lamports == 0
@@ -653,6 +744,70 @@ impl InvokeContextCallback for MolluskInvokeContextCallback<'_> {
     }
 }
 
+/// The top-level instructions executed by `process_transaction_message`.
+#[derive(Clone, Copy)]
+enum TopLevelInstructions<'a> {
+    /// Every instruction in a compiled message.
+    Message(&'a SanitizedMessage),
+    /// A single instruction against pre-compiled instruction accounts. See
+    /// `Mollusk::process_instruction_raw`.
+    Raw {
+        program_id: &'a Pubkey,
+        program_id_index: IndexOfAccount,
+        instruction_accounts: &'a [InstructionAccount],
+        data: &'a [u8],
+    },
+}
+
+impl<'a> TopLevelInstructions<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Message(message) => message.program_instructions_iter().count(),
+            Self::Raw { .. } => 1,
+        }
+    }
+
+    /// Configure the transaction context for the instruction at `index`,
+    /// returning its program ID and data.
+    fn prepare(
+        &self,
+        index: usize,
+        invoke_context: &mut InvokeContext<'_, 'a>,
+    ) -> (&'a Pubkey, &'a [u8]) {
+        match *self {
+            Self::Message(message) => {
+                let (program_id, compiled_ix) =
+                    message.program_instructions_iter().nth(index).unwrap();
+                invoke_context
+                    .prepare_next_top_level_instruction(
+                        message,
+                        &SVMInstruction::from(compiled_ix),
+                        compiled_ix.program_id_index as IndexOfAccount,
+                        &compiled_ix.data,
+                    )
+                    .expect("failed to prepare instruction");
+                (program_id, &compiled_ix.data)
+            }
+            Self::Raw {
+                program_id,
+                program_id_index,
+                instruction_accounts,
+                data,
+            } => {
+                invoke_context
+                    .transaction_context
+                    .configure_next_instruction_for_tests(
+                        program_id_index,
+                        instruction_accounts.to_vec(),
+                        data.to_vec(),
+                    )
+                    .expect("failed to prepare instruction");
+                (program_id, data)
+            }
+        }
+    }
+}
+
 struct MessageResult {
     /// The number of compute units consumed by the transaction.
     pub compute_units_consumed: u64,
@@ -675,9 +830,13 @@ struct MessageResult {
     /// their corresponding pubkeys via `message.account_keys()`.
     ///
     /// This is `None` when the result is loaded from a fuzz fixture, since
-    /// fixtures don't contain the compiled message.
+    /// fixtures don't contain the compiled message, or when the instruction
+    /// was processed with `Mollusk::process_instruction_raw`.
     #[cfg(feature = "inner-instructions")]
     pub message: Option<SanitizedMessage>,
+    /// The full execution timings collected by the runtime.
+    #[cfg(feature = "detailed-timings")]
+    pub timings: ExecuteTimings,
 }
 
 impl MessageResult {
@@ -708,6 +867,9 @@ impl MessageResult {
 
 impl Mollusk {
     fn new_inner(#[allow(unused)] enable_register_tracing: bool) -> Self {
+        // Skipped with `no-default-logger`, for test binaries that configure
+        // their own logger and don't want it replaced.
+        #[cfg(not(feature = "no-default-logger"))]
         #[rustfmt::skip]
         solana_logger::setup_with_default(
             "solana_rbpf::vm=debug,\
@@ -734,13 +896,20 @@ impl Mollusk {
 
         #[allow(unused_mut)]
         let mut me = Self {
-            config: Config::default(),
+            config: Config {
+                update_compute_units: std::env::var_os("MOLLUSK_UPDATE_CU").is_some(),
+                ..Default::default()
+            },
             compute_budget,
             epoch_stake: EpochStake::default(),
+            epoch_stake_by_epoch: EpochStakeByEpoch::default(),
             feature_set,
             logger: None,
             program_cache,
             sysvars: Sysvars::default(),
+            blockhash: Hash::default(),
+            lamports_per_signature: 5000, // The default value
+            account_data_size_limit: None,
 
             #[cfg(feature = "invocation-inspect-callback")]
             invocation_inspect_callback: Box::new(EmptyInvocationInspectCallback {}),
@@ -750,6 +919,10 @@ impl Mollusk {
 
             #[cfg(feature = "fuzz-fd")]
             slot: 0,
+
+            log_writer: None,
+
+            program_runtime_environments: RefCell::new(None),
         };
 
         #[cfg(feature = "register-tracing")]
@@ -782,6 +955,34 @@ impl Mollusk {
         mollusk
     }
 
+    /// Create a new Mollusk instance with exactly the provided features
+    /// active, such as a list exported from a cluster with
+    /// `solana feature status`.
+    ///
+    /// Starting from an empty feature set, each feature is activated at slot
+    /// 0, and the program cache is rebuilt against the resulting feature set.
+    pub fn with_active_features(features: &[Pubkey]) -> Self {
+        let mut feature_set = FeatureSet::default();
+        features
+            .iter()
+            .for_each(|feature_id| feature_set.activate(feature_id, 0));
+
+        let mut mollusk = Self::default();
+
+        #[cfg(feature = "invocation-inspect-callback")]
+        let enable_register_tracing = mollusk.enable_register_tracing;
+        #[cfg(not(feature = "invocation-inspect-callback"))]
+        let enable_register_tracing = false;
+
+        mollusk.program_cache = ProgramCache::new(
+            &feature_set,
+            &mollusk.compute_budget,
+            enable_register_tracing,
+        );
+        mollusk.feature_set = feature_set;
+        mollusk
+    }
+
     /// Create a new Mollusk instance with configurable debugging features.
     ///
     /// This constructor allows enabling low-level VM debugging capabilities,
@@ -811,6 +1012,22 @@ impl Mollusk {
         self.add_program_with_loader(program_id, program_name, &DEFAULT_LOADER_KEY);
     }
 
+    /// Add many programs to the test environment at once.
+    ///
+    /// Each entry is a program ID and the name of its ELF file, loaded the
+    /// same way as in `add_program`.
+    pub fn add_programs(&mut self, programs: &[(Pubkey, &str)]) {
+        self.add_programs_with_loader(programs, &DEFAULT_LOADER_KEY);
+    }
+
+    /// Add many programs to the test environment at once, all under the
+    /// specified loader.
+    pub fn add_programs_with_loader(&mut self, programs: &[(Pubkey, &str)], loader_key: &Pubkey) {
+        for (program_id, program_name) in programs {
+            self.add_program_with_loader(program_id, program_name, loader_key);
+        }
+    }
+
     /// Add a program to the test environment under the specified loader.
     ///
     /// If you intend to CPI to a program, this is likely what you want to use.
@@ -837,22 +1054,360 @@ impl Mollusk {
         self.program_cache.add_program(program_id, loader_key, elf);
     }
 
+    /// Add a program that does nothing but return success to the test
+    /// environment, under the default loader.
+    ///
+    /// The program's ELF is generated by the harness, so no build artifact is
+    /// required. See `program::create_noop_program_elf`.
+    pub fn add_noop_program(&mut self, program_id: &Pubkey) {
+        self.add_program_with_loader_and_elf(
+            program_id,
+            &DEFAULT_LOADER_KEY,
+            &crate::program::create_noop_program_elf(),
+        );
+    }
+
+    /// Insert a prebuilt `ProgramCacheEntry` into the program cache, such as
+    /// a tombstone or delay-visibility entry.
+    ///
+    /// Warning: This is intended for testing loader internals. The entry is
+    /// inserted as-is, without any verification. See
+    /// `ProgramCache::insert_entry` for details.
+    pub fn insert_program_cache_entry(
+        &mut self,
+        program_id: &Pubkey,
+        entry: Arc<ProgramCacheEntry>,
+    ) {
+        self.program_cache.insert_entry(program_id, entry);
+    }
+
+    /// Remove a builtin program, such as the System program, from the program
+    /// cache.
+    ///
+    /// Useful for asserting a program's behavior when a builtin it depends on
+    /// is unavailable. Any instruction targeting the removed builtin will fail
+    /// with `InstructionError::UnsupportedProgramId`, as long as its program
+    /// account is provided.
+    pub fn remove_builtin(&mut self, program_id: &Pubkey) {
+        self.program_cache.remove_program(program_id);
+    }
+
+    /// Set the blockhash provided to the program runtime environment
+    /// (`EnvironmentConfig::blockhash`), which defaults to `Hash::default()`.
+    ///
+    /// This is the blockhash the runtime itself observes, such as when the
+    /// System program stores a durable nonce. It is not reflected in any
+    /// sysvar: to control the hashes a program reads on-chain, configure the
+    /// `SlotHashes` sysvar through `Mollusk::sysvars`.
+    pub fn set_blockhash(&mut self, blockhash: Hash) {
+        self.blockhash = blockhash;
+    }
+
+    /// Set the lamports per signature associated with the blockhash
+    /// (`EnvironmentConfig::blockhash_lamports_per_signature`), which
+    /// defaults to `5000`.
+    pub fn set_lamports_per_signature(&mut self, lamports_per_signature: u64) {
+        self.lamports_per_signature = lamports_per_signature;
+    }
+
+    /// Limit the net growth of account data allowed across an entire
+    /// transaction, in bytes.
+    ///
+    /// If an instruction leaves the total size of all account data more than
+    /// `limit` bytes larger than it was at the start of the transaction,
+    /// execution fails with
+    /// `InstructionError::MaxAccountsDataAllocationsExceeded`.
+    ///
+    /// Note: This only approximates the runtime, which checks the limit on
+    /// every reallocation. Here, the net growth of every account in the
+    /// transaction context is checked after each top-level instruction
+    /// completes. As a result:
+    ///
+    /// * An instruction which grows account data past the limit and shrinks it
+    ///   back before returning is not caught.
+    /// * Each instruction in `process_instruction_chain` runs in its own
+    ///   transaction context, so growth only accumulates across instructions
+    ///   processed as one transaction, such as with
+    ///   `process_transaction_instructions`.
+    pub fn set_account_data_size_limit(&mut self, limit: u64) {
+        self.account_data_size_limit = Some(limit);
+    }
+
+    /// Set the maximum length of the instruction trace, which bounds the
+    /// total number of instructions - top-level and CPI - that may be
+    /// executed within a single transaction context.
+    ///
+    /// Exceeding it fails execution with
+    /// `InstructionError::MaxInstructionTraceLengthExceeded`. This is useful
+    /// for cutting off a program that recursively invokes itself or another
+    /// program, even when testing with a very high compute budget. Note that
+    /// loops within a single instruction are bounded only by the compute
+    /// budget.
+    ///
+    /// This is a shorthand for setting
+    /// `compute_budget.max_instruction_trace_length`.
+    pub fn set_max_trace_length(&mut self, max_instruction_trace_length: usize) {
+        self.compute_budget.max_instruction_trace_length = max_instruction_trace_length;
+    }
+
+    /// Stream program logs to the provided writer, one message per line, as
+    /// each instruction or transaction finishes processing.
+    ///
+    /// If `logger` is set, logs are still collected there as well. Otherwise,
+    /// logs are only written to the writer. Clones of this instance share the
+    /// same writer.
+    pub fn set_log_writer(&mut self, writer: Box<dyn Write>) {
+        self.log_writer = Some(Rc::new(RefCell::new(writer)));
+    }
+
     /// Warp the test environment to a slot by updating sysvars.
     pub fn warp_to_slot(&mut self, slot: u64) {
         self.sysvars.warp_to_slot(slot)
     }
 
+    /// Overwrite the test environment's sysvars with the contents of the
+    /// provided sysvar accounts, ie. to replay captured cluster state.
+    ///
+    /// See `Sysvars::set_from_accounts`.
+    pub fn set_sysvars_from_accounts(&mut self, accounts: &[(Pubkey, Account)]) {
+        self.sysvars.set_from_accounts(accounts)
+    }
+
+    // The epoch stake for the current `Clock` epoch.
+    fn current_epoch_stake(&self) -> &EpochStake {
+        self.epoch_stake_by_epoch
+            .get(&self.sysvars.clock.epoch)
+            .unwrap_or(&self.epoch_stake)
+    }
+
+    /// Perform a pre-flight check of an instruction's account privileges
+    /// against the provided accounts, without executing it.
+    ///
+    /// Returns a descriptive error for obvious misconfigurations that would
+    /// otherwise only surface at execution time, such as:
+    ///
+    /// * An account required by the instruction was not provided.
+    /// * The target program is not provided and not in the program cache.
+    /// * The target program is marked writable. Program IDs are always demoted
+    ///   to readonly.
+    /// * An executable account is marked writable.
+    ///
+    /// Note: The privileges requested by CPIs can't be known before
+    /// execution. To check a CPI against its caller, see
+    /// `validate_cpi_privileges`.
+    pub fn validate_instruction_privileges(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Result<(), String> {
+        let find_account = |pubkey: &Pubkey| {
+            accounts
+                .iter()
+                .find(|(key, _)| key == pubkey)
+                .map(|(_, account)| account)
+        };
+
+        let program_id = &instruction.program_id;
+        if find_account(program_id).is_none()
+            && !crate::program::precompile_keys::is_precompile(program_id)
+            && self.program_cache.load_program(program_id).is_none()
+        {
+            return Err(format!(
+                "Program {program_id} is not provided and not in the program cache"
+            ));
+        }
+
+        for (index, meta) in instruction.accounts.iter().enumerate() {
+            let account = find_account(&meta.pubkey);
+
+            if account.is_none()
+                && meta.pubkey != *program_id
+                && meta.pubkey != solana_instructions_sysvar::ID
+            {
+                return Err(format!(
+                    "Account {} (instruction account index {index}) was not provided",
+                    meta.pubkey,
+                ));
+            }
+
+            if meta.is_writable {
+                if meta.pubkey == *program_id {
+                    return Err(format!(
+                        "Program {} (instruction account index {index}) is marked writable, but \
+                         program IDs are always demoted to readonly",
+                        meta.pubkey,
+                    ));
+                }
+                if account.is_some_and(|account| account.executable()) {
+                    return Err(format!(
+                        "Executable account {} (instruction account index {index}) is marked \
+                         writable",
+                        meta.pubkey,
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the privileges requested by a CPI against the privileges
+    /// granted to its caller, without executing either.
+    ///
+    /// Returns a descriptive error if the CPI requests an account as writable
+    /// when the caller only has it as readonly, or passes an account the
+    /// caller was never given. Either would fail at execution time with
+    /// `InstructionError::PrivilegeEscalation` or
+    /// `InstructionError::MissingAccount`.
+    ///
+    /// Note: Signer escalation is not checked, since the caller may sign for
+    /// a PDA it owns.
+    pub fn validate_cpi_privileges(caller: &Instruction, cpi: &Instruction) -> Result<(), String> {
+        for (index, meta) in cpi.accounts.iter().enumerate() {
+            let caller_metas = caller
+                .accounts
+                .iter()
+                .filter(|caller_meta| caller_meta.pubkey == meta.pubkey);
+
+            let mut provided = meta.pubkey == caller.program_id;
+            let mut writable = false;
+            for caller_meta in caller_metas {
+                provided = true;
+                writable |= caller_meta.is_writable;
+            }
+
+            if !provided {
+                return Err(format!(
+                    "Account {} (CPI account index {index}) was not provided to the caller",
+                    meta.pubkey,
+                ));
+            }
+
+            if meta.is_writable && (!writable || meta.pubkey == caller.program_id) {
+                return Err(format!(
+                    "Account {} (CPI account index {index}) is marked writable in the CPI, but is \
+                     readonly in the calling instruction",
+                    meta.pubkey,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark the program derived address (PDA) produced by the provided signer
+    /// seeds as a signer on the instruction.
+    ///
+    /// On-chain, a PDA can only sign through `invoke_signed`, where a program
+    /// provides the seeds - including the bump seed - for addresses derived
+    /// from its own program ID. When processing an instruction directly with
+    /// Mollusk, there's no calling program, so this helper reproduces the
+    /// privileges such a CPI would grant, allowing a program under test that
+    /// expects a signed PDA to be invoked as if it were.
+    ///
+    /// The `seeds` are the same as those passed to `invoke_signed`, and
+    /// `program_id` is the program the address is derived from. Panics if
+    /// the seeds don't derive a valid program address, or if the address is
+    /// not one of the instruction's accounts.
+    pub fn instruction_with_pda_signer(
+        mut instruction: Instruction,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> Instruction {
+        let pda = Pubkey::create_program_address(seeds, program_id)
+            .or_panic_with(MolluskError::InvalidSignerSeeds(program_id));
+
+        let mut found = false;
+        instruction
+            .accounts
+            .iter_mut()
+            .filter(|meta| meta.pubkey == pda)
+            .for_each(|meta| {
+                meta.is_signer = true;
+                found = true;
+            });
+
+        if !found {
+            panic!("{}", MolluskError::SignerNotInInstruction(&pda));
+        }
+
+        instruction
+    }
+
+    /// Check whether a program is in the program cache.
+    ///
+    /// Useful for debugging instructions or CPIs failing with
+    /// `InstructionError::UnsupportedProgramId`, which is what the runtime
+    /// returns when the invoked program isn't cached.
+    pub fn is_program_cached(&self, program_id: &Pubkey) -> bool {
+        self.program_cache.load_program(program_id).is_some()
+    }
+
+    // Whether the program is a builtin or precompile, rather than BPF.
+    fn is_builtin_program(&self, program_id: &Pubkey) -> bool {
+        crate::program::precompile_keys::is_precompile(program_id)
+            || self
+                .program_cache
+                .load_program(program_id)
+                .is_some_and(|entry| matches!(entry.program, ProgramCacheEntryType::Builtin(_)))
+    }
+
     fn get_loader_key(&self, program_id: &Pubkey) -> Pubkey {
         if crate::program::precompile_keys::is_precompile(program_id) {
-            crate::program::loader_keys::NATIVE_LOADER
-        } else {
-            self.program_cache
-                .load_program(program_id)
-                .or_panic_with(MolluskError::ProgramNotCached(program_id))
-                .account_owner()
+            return crate::program::loader_keys::NATIVE_LOADER;
+        }
+        let Some(entry) = self.program_cache.load_program(program_id) else {
+            let cached_program_ids = self
+                .program_cache
+                .cached_program_ids()
+                .iter()
+                .map(Pubkey::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            panic!(
+                "{}\n    [MOLLUSK]: Cached programs: [{}]",
+                MolluskError::ProgramNotCached(program_id),
+                cached_program_ids,
+            );
+        };
+        entry.account_owner()
+    }
+
+    // Ensure a provided program account is owned by the same loader the
+    // program was cached under, since the cache entry is what gets executed.
+    fn validate_program_account_owner(
+        &self,
+        program_id: &Pubkey,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) {
+        if crate::program::precompile_keys::is_precompile(program_id) {
+            return;
+        }
+        let Some(entry) = self.program_cache.load_program(program_id) else {
+            return;
+        };
+        let Some((_, account)) = accounts.iter().find(|(key, _)| key == program_id) else {
+            return;
+        };
+        let loader_key = entry.account_owner();
+        if account.owner() != &loader_key {
+            panic!(
+                "{}",
+                MolluskError::ProgramLoaderMismatch(program_id, account.owner(), &loader_key)
+            );
         }
     }
 
+    /// Every repeated occurrence of a key in `accounts`, in order.
+    fn duplicate_account_keys(accounts: &[(Pubkey, AccountSharedData)]) -> Vec<&Pubkey> {
+        let mut seen = HashSet::new();
+        accounts
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !seen.insert(*key))
+            .collect()
+    }
+
     // Determine the accounts to fallback to during account compilation.
     fn get_account_fallbacks<'a>(
         &self,
@@ -863,11 +1418,21 @@ impl Mollusk {
         // Use a HashSet for fast lookups.
         let account_keys: HashSet<&Pubkey> = accounts.iter().map(|(key, _)| key).collect();
 
+        if self.config.warn_on_duplicate_accounts && account_keys.len() != accounts.len() {
+            Self::duplicate_account_keys(accounts)
+                .into_iter()
+                .for_each(|key| {
+                    eprintln!("    [MOLLUSK]: Warning: Duplicate account provided: {key}");
+                });
+        }
+
         let mut fallbacks = HashMap::new();
 
         // Top-level target programs.
         all_program_ids.for_each(|program_id| {
-            if !account_keys.contains(program_id) {
+            if account_keys.contains(program_id) {
+                self.validate_program_account_owner(program_id, accounts);
+            } else {
                 // Fallback to a stub.
                 fallbacks.insert(
                     *program_id,
@@ -894,13 +1459,14 @@ impl Mollusk {
 
     fn create_transaction_context(
         &self,
+        compute_budget: &ComputeBudget,
         transaction_accounts: Vec<(Pubkey, AccountSharedData)>,
     ) -> TransactionContext<'_> {
         TransactionContext::new(
             transaction_accounts,
             self.sysvars.rent.clone(),
-            self.compute_budget.max_instruction_stack_depth,
-            self.compute_budget.max_instruction_trace_length,
+            compute_budget.max_instruction_stack_depth,
+            compute_budget.max_instruction_trace_length,
         )
     }
 
@@ -964,20 +1530,7 @@ impl Mollusk {
             .collect()
     }
 
-    fn process_transaction_message<'a>(
-        &self,
-        sanitized_message: &'a SanitizedMessage,
-        transaction_context: &mut TransactionContext<'a>,
-        sysvar_cache: &SysvarCache,
-    ) -> MessageResult {
-        let mut compute_units_consumed = 0;
-        let mut timings = ExecuteTimings::default();
-
-        let mut program_cache = self.program_cache.cache();
-        let callback = MolluskInvokeContextCallback {
-            epoch_stake: &self.epoch_stake,
-            feature_set: &self.feature_set,
-        };
+    fn create_program_runtime_environments(&self) -> ProgramRuntimeEnvironments {
         let execution_budget = self.compute_budget.to_budget();
         let runtime_features = self.feature_set.runtime_features();
 
@@ -985,7 +1538,7 @@ impl Mollusk {
         #[cfg(feature = "register-tracing")]
         let _enable_register_tracing = self.enable_register_tracing;
 
-        let program_runtime_environments: ProgramRuntimeEnvironments = ProgramRuntimeEnvironments {
+        ProgramRuntimeEnvironments {
             program_runtime_v1: Arc::new(
                 create_program_runtime_environment_v1(
                     &runtime_features,
@@ -999,40 +1552,129 @@ impl Mollusk {
                 &execution_budget,
                 /* debugging_features */ _enable_register_tracing,
             )),
+        }
+    }
+
+    // Get the program runtime environments, only rebuilding them if the
+    // compute budget or feature set changed since they were last built.
+    fn program_runtime_environments(&self) -> ProgramRuntimeEnvironments {
+        let mut cached = self.program_runtime_environments.borrow_mut();
+        match cached.as_ref() {
+            Some(cached)
+                if cached.compute_budget == self.compute_budget
+                    && cached.feature_set == self.feature_set =>
+            {
+                cached.environments.clone()
+            }
+            _ => {
+                let environments = self.create_program_runtime_environments();
+                *cached = Some(CachedRuntimeEnvironments {
+                    compute_budget: self.compute_budget,
+                    feature_set: self.feature_set.clone(),
+                    environments: environments.clone(),
+                });
+                environments
+            }
+        }
+    }
+
+    fn total_accounts_data_len(transaction_context: &TransactionContext) -> u64 {
+        (0..transaction_context.get_number_of_accounts())
+            .map(|index| {
+                let account_ref = transaction_context.accounts().try_borrow(index).unwrap();
+                account_ref.data().len() as u64
+            })
+            .sum()
+    }
+
+    fn deconstruct_all_accounts(
+        transaction_context: &TransactionContext,
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        (0..transaction_context.get_number_of_accounts())
+            .map(|index| {
+                let pubkey = transaction_context
+                    .get_key_of_account_at_index(index)
+                    .unwrap();
+                let account_ref = transaction_context.accounts().try_borrow(index).unwrap();
+                let account = Account {
+                    lamports: account_ref.lamports(),
+                    data: account_ref.data().to_vec(),
+                    owner: *account_ref.owner(),
+                    executable: account_ref.executable(),
+                    rent_epoch: account_ref.rent_epoch(),
+                }
+                .into();
+                (*pubkey, account)
+            })
+            .collect()
+    }
+
+    fn process_transaction_message<'a>(
+        &self,
+        instructions: TopLevelInstructions<'a>,
+        transaction_context: &mut TransactionContext<'a>,
+        compute_budget: &ComputeBudget,
+        program_runtime_environments: &ProgramRuntimeEnvironments,
+        sysvar_cache: &SysvarCache,
+    ) -> MessageResult {
+        let mut compute_units_consumed = 0;
+        let mut timings = ExecuteTimings::default();
+
+        let mut program_cache = self.program_cache.cache();
+        let callback = MolluskInvokeContextCallback {
+            epoch_stake: self.current_epoch_stake(),
+            feature_set: &self.feature_set,
+        };
+        let runtime_features = self.feature_set.runtime_features();
+
+        // Without a logger of its own, the log writer needs one to collect
+        // logs from.
+        let logger = match (&self.logger, &self.log_writer) {
+            (None, Some(_)) => Some(LogCollector::new_ref()),
+            (logger, _) => logger.clone(),
         };
+        let logs_written = logger
+            .as_ref()
+            .map_or(0, |logger| logger.borrow().get_recorded_content().len());
 
         let mut invoke_context = InvokeContext::new(
             transaction_context,
             &mut program_cache,
             EnvironmentConfig::new(
-                Hash::default(),
-                /* blockhash_lamports_per_signature */ 5000, // The default value
+                self.blockhash,
+                /* blockhash_lamports_per_signature */ self.lamports_per_signature,
                 &callback,
                 &runtime_features,
-                &program_runtime_environments,
-                &program_runtime_environments,
+                program_runtime_environments,
+                program_runtime_environments,
                 sysvar_cache,
             ),
-            self.logger.clone(),
-            self.compute_budget.to_budget(),
-            self.compute_budget.to_cost(),
+            logger.clone(),
+            compute_budget.to_budget(),
+            compute_budget.to_cost(),
         );
 
+        let initial_accounts_data_len = self
+            .account_data_size_limit
+            .map(|_| Self::total_accounts_data_len(invoke_context.transaction_context));
+
         let mut raw_result = Ok(());
 
-        for (instruction_index, (program_id, compiled_ix)) in
-            sanitized_message.program_instructions_iter().enumerate()
-        {
-            let program_id_index = compiled_ix.program_id_index as IndexOfAccount;
-
-            invoke_context
-                .prepare_next_top_level_instruction(
-                    sanitized_message,
-                    &SVMInstruction::from(compiled_ix),
-                    program_id_index,
-                    &compiled_ix.data,
-                )
-                .expect("failed to prepare instruction");
+        for instruction_index in 0..instructions.len() {
+            let (program_id, data) = instructions.prepare(instruction_index, &mut invoke_context);
+
+            // Reject oversized instruction data before it reaches the program.
+            if self
+                .config
+                .max_instruction_data_len
+                .is_some_and(|max_len| data.len() > max_len)
+            {
+                raw_result = Err(TransactionError::InstructionError(
+                    instruction_index as u8,
+                    InstructionError::InvalidInstructionData,
+                ));
+                break;
+            }
 
             #[cfg(feature = "invocation-inspect-callback")]
             {
@@ -1044,18 +1686,14 @@ impl Mollusk {
                 self.invocation_inspect_callback.before_invocation(
                     self,
                     program_id,
-                    &compiled_ix.data,
+                    data,
                     &instruction_accounts,
                     &invoke_context,
                 );
             }
 
             let invoke_result = if invoke_context.is_precompile(program_id) {
-                invoke_context.process_precompile(
-                    program_id,
-                    &compiled_ix.data,
-                    std::iter::once(compiled_ix.data.as_ref()),
-                )
+                invoke_context.process_precompile(program_id, data, std::iter::once(data))
             } else {
                 invoke_context.process_instruction(&mut compute_units_consumed, &mut timings)
             };
@@ -1067,6 +1705,19 @@ impl Mollusk {
                 self.enable_register_tracing,
             );
 
+            // Enforce the account data size limit, if configured.
+            let invoke_result = match (self.account_data_size_limit, initial_accounts_data_len) {
+                (Some(limit), Some(initial_len))
+                    if invoke_result.is_ok()
+                        && Self::total_accounts_data_len(invoke_context.transaction_context)
+                            .saturating_sub(initial_len)
+                            > limit =>
+                {
+                    Err(InstructionError::MaxAccountsDataAllocationsExceeded)
+                }
+                _ => invoke_result,
+            };
+
             if let Err(err) = invoke_result {
                 raw_result = Err(TransactionError::InstructionError(
                     instruction_index as u8,
@@ -1081,6 +1732,19 @@ impl Mollusk {
         #[cfg(feature = "inner-instructions")]
         let inner_instructions = Self::deconstruct_inner_instructions(transaction_context);
 
+        if let (Some(writer), Some(logger)) = (&self.log_writer, &logger) {
+            let mut writer = writer.borrow_mut();
+            for message in logger
+                .borrow()
+                .get_recorded_content()
+                .iter()
+                .skip(logs_written)
+            {
+                let _ = writeln!(writer, "{message}");
+            }
+            let _ = writer.flush();
+        }
+
         MessageResult {
             compute_units_consumed,
             execution_time: timings.details.execute_us.0,
@@ -1089,36 +1753,82 @@ impl Mollusk {
             #[cfg(feature = "inner-instructions")]
             inner_instructions,
             #[cfg(feature = "inner-instructions")]
-            message: Some(sanitized_message.clone()),
+            message: match instructions {
+                TopLevelInstructions::Message(message) => Some(message.clone()),
+                TopLevelInstructions::Raw { .. } => None,
+            },
+            #[cfg(feature = "detailed-timings")]
+            timings,
+        }
+    }
+
+    /// Top up the lamports of the instruction's first signer, if
+    /// `Config::auto_fund_payer` is set.
+    fn fund_payer<'a>(
+        &self,
+        instruction: &Instruction,
+        accounts: &'a [(Pubkey, AccountSharedData)],
+    ) -> Cow<'a, [(Pubkey, AccountSharedData)]> {
+        let Some(lamports) = self.config.auto_fund_payer else {
+            return Cow::Borrowed(accounts);
+        };
+        let mut accounts = accounts.to_vec();
+        let payer = instruction
+            .accounts
+            .iter()
+            .find(|meta| meta.is_signer)
+            .and_then(|payer| accounts.iter_mut().find(|(key, _)| key == &payer.pubkey))
+            .filter(|(_, account)| account.lamports() < lamports);
+        if let Some((_, account)) = payer {
+            account.set_lamports(lamports);
         }
+        Cow::Owned(accounts)
     }
 
-    fn process_instruction_chain_element(
+    #[allow(clippy::too_many_arguments)]
+    fn process_instruction_inner(
         &self,
         index: usize,
         instruction: &Instruction,
         accounts: &[(Pubkey, AccountSharedData)],
         fallback_accounts: &HashMap<Pubkey, AccountSharedData>,
+        compute_budget: &ComputeBudget,
+        program_runtime_environments: &ProgramRuntimeEnvironments,
         sysvar_cache: &SysvarCache,
+        captured_accounts: Option<&mut Vec<(Pubkey, AccountSharedData)>>,
+        collect_resulting_accounts: bool,
     ) -> InstructionResult {
+        let funded_accounts = self.fund_payer(instruction, accounts);
+
         let (sanitized_message, transaction_accounts) = crate::compile_accounts::compile_accounts(
             std::slice::from_ref(instruction),
-            accounts.iter(),
+            funded_accounts.iter(),
             fallback_accounts,
+            None,
         );
 
-        let mut transaction_context = self.create_transaction_context(transaction_accounts);
+        let mut transaction_context =
+            self.create_transaction_context(compute_budget, transaction_accounts);
         transaction_context.set_top_level_instruction_index(index);
 
         let message_result = self.process_transaction_message(
-            &sanitized_message,
+            TopLevelInstructions::Message(&sanitized_message),
             &mut transaction_context,
+            compute_budget,
+            program_runtime_environments,
             sysvar_cache,
         );
 
-        let resulting_accounts = if message_result.raw_result.is_ok() {
-            Self::deconstruct_resulting_accounts(&transaction_context, accounts)
+        if let Some(captured_accounts) = captured_accounts {
+            *captured_accounts = Self::deconstruct_all_accounts(&transaction_context);
+        }
+
+        let resulting_accounts = if !collect_resulting_accounts {
+            Vec::new()
+        } else if message_result.raw_result.is_ok() || self.config.capture_accounts_on_failure {
+            Self::deconstruct_resulting_accounts(&transaction_context, &funded_accounts)
         } else {
+            // Lamports minted for the payer are discarded on failure.
             accounts.to_vec()
         };
 
@@ -1126,8 +1836,17 @@ impl Mollusk {
             .raw_result
             .map_err(MessageResult::extract_ix_err);
 
+        let (builtin_compute_units, bpf_compute_units) =
+            if self.is_builtin_program(&instruction.program_id) {
+                (message_result.compute_units_consumed, 0)
+            } else {
+                (0, message_result.compute_units_consumed)
+            };
+
         InstructionResult {
             compute_units_consumed: message_result.compute_units_consumed,
+            builtin_compute_units,
+            bpf_compute_units,
             execution_time: message_result.execution_time,
             program_result: raw_result.clone().into(),
             raw_result,
@@ -1141,12 +1860,19 @@ impl Mollusk {
                 .unwrap_or_default(),
             #[cfg(feature = "inner-instructions")]
             message: message_result.message,
+            #[cfg(feature = "detailed-timings")]
+            timings: Some(message_result.timings.into()),
         }
     }
 
     /// Process an instruction using the minified Solana Virtual Machine (SVM)
     /// environment. Simply returns the result.
     ///
+    /// Accounts are taken as `AccountSharedData`, so callers already holding
+    /// shared accounts (ie. from an AccountsDB snapshot) can pass them
+    /// directly, without converting from `Account`. The provided accounts are
+    /// never modified.
+    ///
     /// For `fuzz` feature only:
     ///
     /// If the `EJECT_FUZZ_FIXTURES` environment variable is set, this function
@@ -1176,33 +1902,147 @@ impl Mollusk {
             accounts,
         );
 
-        let (sanitized_message, transaction_accounts) = crate::compile_accounts::compile_accounts(
-            std::slice::from_ref(instruction),
-            accounts.iter(),
+        self.process_instruction_inner(
+            0,
+            instruction,
+            accounts,
+            &fallback_accounts,
+            &self.compute_budget,
+            &self.program_runtime_environments(),
+            &self.sysvars.setup_sysvar_cache(accounts),
+            None,
+            self.config.collect_resulting_accounts,
+        )
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, returning the raw error if the instruction failed.
+    ///
+    /// This is a convenience over `process_instruction` for helpers that wish
+    /// to propagate failures with `?`. The full `InstructionResult` is only
+    /// returned on success.
+    pub fn try_process_instruction(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Result<InstructionResult, InstructionError> {
+        let result = self.process_instruction(instruction, accounts);
+        result.raw_result.clone().map(|()| result)
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, also capturing the state of every account in the
+    /// transaction context after execution.
+    ///
+    /// Unlike `InstructionResult::resulting_accounts`, which only contains the
+    /// provided accounts, the captured accounts include everything loaded into
+    /// the transaction context. Beyond the provided accounts, that's the
+    /// top-level program account and any accounts stubbed out by Mollusk,
+    /// such as the instructions sysvar.
+    ///
+    /// Note: A CPI can only access accounts already in the transaction
+    /// context, so any account it modifies must be passed in the top-level
+    /// instruction's metas.
+    ///
+    /// Note: The captured accounts reflect the transaction context as-is, even
+    /// if the instruction failed. They are not rolled back.
+    pub fn process_instruction_capturing_context(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> (InstructionResult, Vec<(Pubkey, AccountSharedData)>) {
+        let fallback_accounts = self.get_account_fallbacks(
+            std::iter::once(&instruction.program_id),
+            std::iter::once(instruction),
+            accounts,
+        );
+
+        let mut captured_accounts = Vec::new();
+        let result = self.process_instruction_inner(
+            0,
+            instruction,
+            accounts,
             &fallback_accounts,
+            &self.compute_budget,
+            &self.program_runtime_environments(),
+            &self.sysvars.setup_sysvar_cache(accounts),
+            Some(&mut captured_accounts),
+            self.config.collect_resulting_accounts,
         );
 
-        let mut transaction_context = self.create_transaction_context(transaction_accounts);
-        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
+        (result, captured_accounts)
+    }
+
+    /// Process an instruction against raw, pre-compiled instruction accounts
+    /// and transaction accounts, bypassing Mollusk's account compilation
+    /// entirely.
+    ///
+    /// **Warning:** This is an escape hatch for reproducing exotic scenarios,
+    /// such as unusual duplicate-account layouts or account orderings, which
+    /// can't be expressed through an `Instruction`. None of Mollusk's usual
+    /// conveniences apply:
+    ///
+    /// * Only the program ID and data of `instruction` are used. Its account
+    ///   metas are ignored in favor of `instruction_accounts`.
+    /// * `program_id_index` and each `InstructionAccount::index_in_transaction`
+    ///   index into `transaction_accounts`, which is loaded as-is. Nothing is
+    ///   deduplicated, stubbed, or filled in.
+    /// * The program account and the instructions sysvar must be provided
+    ///   manually, if needed.
+    /// * Privileges are taken from `instruction_accounts` as-is, without any
+    ///   validation.
+    ///
+    /// Mismatches will cause confusing runtime errors or panics. Prefer
+    /// `process_instruction` wherever possible.
+    ///
+    /// Resulting accounts are returned in the same order as
+    /// `transaction_accounts`.
+    pub fn process_instruction_raw(
+        &self,
+        instruction: &Instruction,
+        program_id_index: IndexOfAccount,
+        instruction_accounts: &[InstructionAccount],
+        transaction_accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        let mut transaction_context =
+            self.create_transaction_context(&self.compute_budget, transaction_accounts.to_vec());
+        let sysvar_cache = self.sysvars.setup_sysvar_cache(transaction_accounts);
 
         let message_result = self.process_transaction_message(
-            &sanitized_message,
+            TopLevelInstructions::Raw {
+                program_id: &instruction.program_id,
+                program_id_index,
+                instruction_accounts,
+                data: &instruction.data,
+            },
             &mut transaction_context,
+            &self.compute_budget,
+            &self.program_runtime_environments(),
             &sysvar_cache,
         );
 
-        let resulting_accounts = if message_result.raw_result.is_ok() {
-            Self::deconstruct_resulting_accounts(&transaction_context, accounts)
-        } else {
-            accounts.to_vec()
-        };
+        let resulting_accounts =
+            if message_result.raw_result.is_ok() || self.config.capture_accounts_on_failure {
+                Self::deconstruct_resulting_accounts(&transaction_context, transaction_accounts)
+            } else {
+                transaction_accounts.to_vec()
+            };
 
         let raw_result = message_result
             .raw_result
             .map_err(MessageResult::extract_ix_err);
 
+        let (builtin_compute_units, bpf_compute_units) =
+            if self.is_builtin_program(&instruction.program_id) {
+                (message_result.compute_units_consumed, 0)
+            } else {
+                (0, message_result.compute_units_consumed)
+            };
+
         InstructionResult {
             compute_units_consumed: message_result.compute_units_consumed,
+            builtin_compute_units,
+            bpf_compute_units,
             execution_time: message_result.execution_time,
             program_result: raw_result.clone().into(),
             raw_result,
@@ -1212,10 +2052,12 @@ impl Mollusk {
             inner_instructions: message_result
                 .inner_instructions
                 .into_iter()
-                .next()
-                .unwrap_or_default(),
+                .flatten()
+                .collect(),
             #[cfg(feature = "inner-instructions")]
             message: message_result.message,
+            #[cfg(feature = "detailed-timings")]
+            timings: Some(message_result.timings.into()),
         }
     }
 
@@ -1227,6 +2069,9 @@ impl Mollusk {
     ///   instructions.
     /// * `execution_time`: The total execution time across all instructions.
     /// * `program_result`: The program result of the _last_ instruction.
+    /// * `return_data`: The return data set by the _last_ instruction. As in
+    ///   the runtime, return data is never carried over from a previous
+    ///   instruction, so this is empty if the last instruction didn't set any.
     /// * `resulting_accounts`: The resulting accounts after the _last_
     ///   instruction.
     ///
@@ -1271,15 +2116,189 @@ impl Mollusk {
             accounts,
         );
 
+        let program_runtime_environments = self.program_runtime_environments();
         let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
 
         for (index, instruction) in instructions.iter().enumerate() {
-            let this_result = self.process_instruction_chain_element(
+            let this_result = self.process_instruction_inner(
+                index,
+                instruction,
+                &composite_result.resulting_accounts,
+                &fallback_accounts,
+                &self.compute_budget,
+                &program_runtime_environments,
+                &sysvar_cache,
+                None,
+                /* collect_resulting_accounts */ true,
+            );
+
+            composite_result.absorb(this_result);
+
+            if composite_result.program_result.is_err() {
+                break;
+            }
+        }
+
+        composite_result
+    }
+
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment.
+    ///
+    /// Identical to `process_instruction_chain`, but also retains the result
+    /// of every step, so the compute units consumed by each step can be
+    /// inspected, ie. with `ChainResult::step_compute_units`.
+    pub fn process_instruction_chain_retaining_steps(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> ChainResult {
+        let instructions = instructions
+            .iter()
+            .map(|instruction| (instruction, &[] as &[Check]))
+            .collect::<Vec<_>>();
+        let mut steps = Vec::with_capacity(instructions.len());
+        let composite = self.process_and_validate_instruction_chain_inner(
+            &instructions,
+            accounts,
+            &self.config,
+            /* stop_on_failed_checks */ false,
+            Some(&mut steps),
+        );
+        let steps = steps.into_iter().map(|(step, _)| step).collect();
+        ChainResult { steps, composite }
+    }
+
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment, asserting that lamports are conserved after
+    /// every step.
+    ///
+    /// Lamports sent to any of `burn_sinks` are considered burned: after each
+    /// step, the total lamports held by all other accounts must equal their
+    /// starting total, minus whatever the sinks have received so far.
+    ///
+    /// Panics with the offending step's index if the invariant is violated.
+    pub fn process_instruction_chain_conserving(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        burn_sinks: &[Pubkey],
+    ) -> InstructionResult {
+        // Returns the total lamports held outside and inside the sinks.
+        let totals = |accounts: &[(Pubkey, AccountSharedData)]| {
+            accounts
+                .iter()
+                .fold((0i128, 0i128), |(held, burned), (pubkey, account)| {
+                    let lamports = i128::from(account.lamports());
+                    if burn_sinks.contains(pubkey) {
+                        (held, burned + lamports)
+                    } else {
+                        (held + lamports, burned)
+                    }
+                })
+        };
+
+        let (starting_held, starting_burned) = totals(accounts);
+
+        let chain = self.process_instruction_chain_retaining_steps(instructions, accounts);
+        for (index, step) in chain.steps.iter().enumerate() {
+            let (held, burned) = totals(&step.resulting_accounts);
+            let expected = starting_held - (burned - starting_burned);
+            if held != expected {
+                panic!(
+                    "    [MOLLUSK]: Lamports were not conserved after step {index}: expected \
+                     total {expected}, got {held} (net difference: {})",
+                    held - expected,
+                );
+            }
+        }
+
+        chain.composite
+    }
+
+    /// Process many independent instructions using the minified Solana
+    /// Virtual Machine (SVM) environment, all starting from the same
+    /// accounts.
+    ///
+    /// Unlike a chain, no state is carried over: each instruction sees the
+    /// original `accounts`, and its result reflects only its own changes.
+    /// Results are returned in the same order as the instructions.
+    pub fn process_instructions_independent(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Vec<InstructionResult> {
+        let program_runtime_environments = self.program_runtime_environments();
+        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
+
+        instructions
+            .iter()
+            .map(|instruction| {
+                let fallback_accounts = self.get_account_fallbacks(
+                    once(&instruction.program_id),
+                    once(instruction),
+                    accounts,
+                );
+
+                self.process_instruction_inner(
+                    0,
+                    instruction,
+                    accounts,
+                    &fallback_accounts,
+                    &self.compute_budget,
+                    &program_runtime_environments,
+                    &sysvar_cache,
+                    None,
+                    self.config.collect_resulting_accounts,
+                )
+            })
+            .collect()
+    }
+
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment, applying a separate compute unit limit to
+    /// each step.
+    ///
+    /// Useful for finding which step of a chain is the compute bottleneck.
+    /// Otherwise, this behaves exactly like `process_instruction_chain`, and
+    /// the composite result sums the compute units consumed by each step.
+    ///
+    /// Each step runs under a scratch copy of the compute budget, so the
+    /// harness's own budget is left untouched.
+    pub fn process_instruction_chain_with_budgets(
+        &self,
+        steps: &[(&Instruction, u64)],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> InstructionResult {
+        let mut composite_result = InstructionResult {
+            resulting_accounts: accounts.to_vec(),
+            ..Default::default()
+        };
+
+        let fallback_accounts = self.get_account_fallbacks(
+            steps.iter().map(|(ix, _)| &ix.program_id),
+            steps.iter().map(|(ix, _)| *ix),
+            accounts,
+        );
+
+        let program_runtime_environments = self.program_runtime_environments();
+        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
+
+        let mut compute_budget = self.compute_budget;
+
+        for (index, (instruction, compute_unit_limit)) in steps.iter().enumerate() {
+            compute_budget.compute_unit_limit = *compute_unit_limit;
+
+            let this_result = self.process_instruction_inner(
                 index,
                 instruction,
                 &composite_result.resulting_accounts,
                 &fallback_accounts,
+                &compute_budget,
+                &program_runtime_environments,
                 &sysvar_cache,
+                None,
+                /* collect_resulting_accounts */ true,
             );
 
             composite_result.absorb(this_result);
@@ -1292,6 +2311,107 @@ impl Mollusk {
         composite_result
     }
 
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, with the provided features activated (`true`) or
+    /// deactivated (`false`) for this call only.
+    ///
+    /// Useful for testing feature-gated behavior from a single harness. The
+    /// instruction runs on a clone of the harness, so its feature set is left
+    /// untouched.
+    ///
+    /// Cached programs are reloaded under the overridden feature set, so
+    /// features affecting program loading, such as syscall availability or
+    /// supported SBPF versions, apply as well. Only programs added from an ELF
+    /// can be reloaded. Builtins and entries inserted with
+    /// `insert_program_cache_entry` are used as-is.
+    pub fn process_instruction_with_features(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        overrides: &[(Pubkey, bool)],
+    ) -> InstructionResult {
+        let mut mollusk = self.clone();
+        for (feature_id, active) in overrides {
+            if *active {
+                mollusk.feature_set.activate(feature_id, 0);
+            } else {
+                mollusk.feature_set.deactivate(feature_id);
+            }
+        }
+
+        #[cfg(feature = "invocation-inspect-callback")]
+        let enable_register_tracing = mollusk.enable_register_tracing;
+        #[cfg(not(feature = "invocation-inspect-callback"))]
+        let enable_register_tracing = false;
+
+        mollusk.program_cache = self.program_cache.reload(
+            &mollusk.feature_set,
+            &mollusk.compute_budget,
+            enable_register_tracing,
+        );
+        mollusk.process_instruction(instruction, accounts)
+    }
+
+    /// Process an instruction with the provided feature deactivated, then
+    /// activated, returning the compute units consumed by each run as
+    /// `(off, on)`.
+    ///
+    /// Useful for measuring the compute unit impact of a feature gate. Both
+    /// runs use `process_instruction_with_features`, so the harness's own
+    /// feature set is left untouched, and cached programs are reloaded under
+    /// each feature set.
+    ///
+    /// Note that the instruction's result is not checked. If the feature
+    /// causes the instruction to fail, the units consumed up to the failure
+    /// are reported.
+    pub fn compute_units_by_feature(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        feature_id: Pubkey,
+    ) -> (u64, u64) {
+        let [off, on] = [false, true].map(|active| {
+            self.process_instruction_with_features(instruction, accounts, &[(feature_id, active)])
+                .compute_units_consumed
+        });
+        (off, on)
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, reading its accounts from the provided map.
+    ///
+    /// If the instruction succeeds, its resulting accounts are written into
+    /// `out`, so a large account map can be reused across many instructions
+    /// without converting it on every call. Accounts are looked up by the
+    /// instruction's account keys, so accounts missing from the map are
+    /// reported as usual.
+    pub fn process_instruction_into(
+        &self,
+        instruction: &Instruction,
+        accounts: &HashMap<Pubkey, AccountSharedData>,
+        out: &mut HashMap<Pubkey, AccountSharedData>,
+    ) -> InstructionResult {
+        let mut seen = HashSet::new();
+        let instruction_accounts = instruction
+            .accounts
+            .iter()
+            .filter(|meta| seen.insert(meta.pubkey))
+            .filter_map(|meta| {
+                accounts
+                    .get(&meta.pubkey)
+                    .map(|account| (meta.pubkey, account.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let result = self.process_instruction(instruction, &instruction_accounts);
+
+        if result.program_result.is_ok() {
+            out.extend(result.resulting_accounts.iter().cloned());
+        }
+
+        result
+    }
+
     /// Process multiple instructions using a single shared transaction context.
     ///
     /// This API is the closest Mollusk offers to a transaction. All
@@ -1312,6 +2432,31 @@ impl Mollusk {
         &self,
         instructions: &[Instruction],
         accounts: &[(Pubkey, AccountSharedData)],
+    ) -> TransactionResult {
+        self.process_transaction_instructions_inner(instructions, accounts, None)
+    }
+
+    /// Process multiple instructions using a single shared transaction
+    /// context, with the provided account designated as the fee payer.
+    ///
+    /// Identical to `process_transaction_instructions`, except the fee payer
+    /// is compiled as the first account of the message, and is always a
+    /// writable signer, matching how the runtime compiles a transaction's
+    /// message. Its account must be provided like any other.
+    pub fn process_transaction_instructions_with_fee_payer(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        fee_payer: &Pubkey,
+    ) -> TransactionResult {
+        self.process_transaction_instructions_inner(instructions, accounts, Some(fee_payer))
+    }
+
+    fn process_transaction_instructions_inner(
+        &self,
+        instructions: &[Instruction],
+        accounts: &[(Pubkey, AccountSharedData)],
+        fee_payer: Option<&Pubkey>,
     ) -> TransactionResult {
         let fallback_accounts = self.get_account_fallbacks(
             instructions.iter().map(|ix| &ix.program_id),
@@ -1323,22 +2468,27 @@ impl Mollusk {
             instructions,
             accounts.iter(),
             &fallback_accounts,
+            fee_payer,
         );
 
-        let mut transaction_context = self.create_transaction_context(transaction_accounts);
+        let mut transaction_context =
+            self.create_transaction_context(&self.compute_budget, transaction_accounts);
         let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
 
         let message_result = self.process_transaction_message(
-            &sanitized_message,
+            TopLevelInstructions::Message(&sanitized_message),
             &mut transaction_context,
+            &self.compute_budget,
+            &self.program_runtime_environments(),
             &sysvar_cache,
         );
 
-        let resulting_accounts = if message_result.raw_result.is_ok() {
-            Self::deconstruct_resulting_accounts(&transaction_context, accounts)
-        } else {
-            accounts.to_vec()
-        };
+        let resulting_accounts =
+            if message_result.raw_result.is_ok() || self.config.capture_accounts_on_failure {
+                Self::deconstruct_resulting_accounts(&transaction_context, accounts)
+            } else {
+                accounts.to_vec()
+            };
 
         let program_result = MessageResult::extract_txn_program_result(&message_result.raw_result);
 
@@ -1356,6 +2506,31 @@ impl Mollusk {
         }
     }
 
+    /// Process a versioned message, such as a v0 message referencing address
+    /// lookup tables, using the minified Solana Virtual Machine (SVM)
+    /// environment.
+    ///
+    /// Any addresses loaded through lookup tables are resolved against the
+    /// provided `lookup_tables` before the message is decompiled into its
+    /// instructions and processed like
+    /// `process_transaction_instructions`. Accounts for the resolved
+    /// addresses must be included in `accounts` like any other.
+    ///
+    /// Panics if a lookup table referenced by the message is not provided, if
+    /// a lookup index is out of bounds for its table, or if the message is
+    /// malformed, ie. its header doesn't fit its account keys or an
+    /// instruction references an account index that doesn't exist.
+    pub fn process_versioned_message(
+        &self,
+        message: &VersionedMessage,
+        lookup_tables: &[AddressLookupTableAccount],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> TransactionResult {
+        let instructions =
+            crate::compile_accounts::decompile_versioned_message(message, lookup_tables);
+        self.process_transaction_instructions(&instructions, accounts)
+    }
+
     /// Process an instruction using the minified Solana Virtual Machine (SVM)
     /// environment, then perform checks on the result. Panics if any checks
     /// fail.
@@ -1385,10 +2560,58 @@ impl Mollusk {
         checks: &[Check],
     ) -> InstructionResult {
         let result = self.process_instruction(instruction, accounts);
-        result.run_checks(checks, &self.config, self);
+        // Compare against the inputs the program actually saw, including any
+        // lamports minted for the payer.
+        let inputs = self.fund_payer(instruction, accounts);
+        result.run_checks_with_inputs(checks, &self.config, self, &inputs);
         result
     }
 
+    /// Perform checks on the input accounts, process an instruction using the
+    /// minified Solana Virtual Machine (SVM) environment, then perform checks
+    /// on the result. Panics if any checks fail.
+    ///
+    /// The `before_checks` are run against a result synthesized from the
+    /// input accounts, so account checks assert the state prior to
+    /// execution, while the `after_checks` assert the state after.
+    pub fn process_and_validate_instruction_with_pre_checks(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        before_checks: &[Check],
+        after_checks: &[Check],
+    ) -> InstructionResult {
+        let before = InstructionResult {
+            resulting_accounts: accounts.to_vec(),
+            ..Default::default()
+        };
+        before.run_checks(before_checks, &self.config, self);
+        self.process_and_validate_instruction(instruction, accounts, after_checks)
+    }
+
+    /// Process an instruction using the minified Solana Virtual Machine (SVM)
+    /// environment, then perform checks on the result, reporting the outcome
+    /// of each check rather than panicking.
+    ///
+    /// Returns the result alongside one entry per check, in order, pairing
+    /// the check's label with whether or not it passed.
+    pub fn process_and_validate_instruction_with_report(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[Check],
+    ) -> (InstructionResult, Vec<(String, bool)>) {
+        let result = self.process_instruction(instruction, accounts);
+        let config = Config {
+            panic: false,
+            ..self.config.clone()
+        };
+        // Compare against the same inputs as `process_and_validate_instruction`.
+        let inputs = self.fund_payer(instruction, accounts);
+        let report = result.run_checks_with_report(checks, &config, self, Some(&inputs));
+        (result, report)
+    }
+
     /// Process a chain of instructions using the minified Solana Virtual
     /// Machine (SVM) environment, then perform checks on the result.
     /// Panics if any checks fail.
@@ -1424,33 +2647,119 @@ impl Mollusk {
         instructions: &[(&Instruction, &[Check])],
         accounts: &[(Pubkey, AccountSharedData)],
     ) -> InstructionResult {
-        let mut composite_result = InstructionResult {
-            resulting_accounts: accounts.to_vec(),
-            ..Default::default()
-        };
+        self.process_and_validate_instruction_chain_inner(
+            instructions,
+            accounts,
+            &self.config,
+            /* stop_on_failed_checks */ false,
+            None,
+        )
+    }
 
-        let fallback_accounts = self.get_account_fallbacks(
-            instructions.iter().map(|(ix, _)| &ix.program_id),
-            instructions.iter().map(|(ix, _)| *ix),
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment, then perform checks on the result of each
+    /// step, followed by checks on the composite result of the entire chain.
+    /// Panics if any checks fail.
+    ///
+    /// The `composite_checks` target the result returned by
+    /// `process_instruction_chain`, so `Check::compute_units` asserts the
+    /// total compute units consumed across every step, and account checks
+    /// assert the state after the last step. Checks comparing against input
+    /// state, such as `Check::lamports_conserved`, compare against the
+    /// accounts provided to the chain.
+    pub fn process_and_validate_instruction_chain_with_composite_checks(
+        &self,
+        instructions: &[(&Instruction, &[Check])],
+        accounts: &[(Pubkey, AccountSharedData)],
+        composite_checks: &[Check],
+    ) -> InstructionResult {
+        let result = self.process_and_validate_instruction_chain_inner(
+            instructions,
             accounts,
+            &self.config,
+            /* stop_on_failed_checks */ false,
+            None,
         );
+        result.run_checks_with_inputs(composite_checks, &self.config, self, accounts);
+        result
+    }
 
-        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
-
-        for (index, (instruction, checks)) in instructions.iter().enumerate() {
-            let this_result = self.process_instruction_chain_element(
-                index,
-                instruction,
-                &composite_result.resulting_accounts,
-                &fallback_accounts,
-                &sysvar_cache,
-            );
-
-            this_result.run_checks(checks, &self.config, self);
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment, then perform checks on the result.
+    /// Panics if any checks fail.
+    ///
+    /// Identical to `process_and_validate_instruction_chain`, but also
+    /// retains the result of every step, so additional checks can be run
+    /// against intermediate steps after the fact.
+    pub fn process_and_validate_instruction_chain_retaining_steps(
+        &self,
+        instructions: &[(&Instruction, &[Check])],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> ChainResult {
+        let mut steps = Vec::with_capacity(instructions.len());
+        let composite = self.process_and_validate_instruction_chain_inner(
+            instructions,
+            accounts,
+            &self.config,
+            /* stop_on_failed_checks */ false,
+            Some(&mut steps),
+        );
+        let steps = steps.into_iter().map(|(step, _)| step).collect();
+        ChainResult { steps, composite }
+    }
+
+    /// Stops after the first step whose program fails, or, if
+    /// `stop_on_failed_checks` is set, whose checks fail. Failing checks only
+    /// panic if `config.panic` is set.
+    fn process_and_validate_instruction_chain_inner(
+        &self,
+        instructions: &[(&Instruction, &[Check])],
+        accounts: &[(Pubkey, AccountSharedData)],
+        config: &Config,
+        stop_on_failed_checks: bool,
+        mut steps: Option<&mut Vec<(InstructionResult, bool)>>,
+    ) -> InstructionResult {
+        let mut composite_result = InstructionResult {
+            resulting_accounts: accounts.to_vec(),
+            ..Default::default()
+        };
+
+        let fallback_accounts = self.get_account_fallbacks(
+            instructions.iter().map(|(ix, _)| &ix.program_id),
+            instructions.iter().map(|(ix, _)| *ix),
+            accounts,
+        );
+
+        let program_runtime_environments = self.program_runtime_environments();
+        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
+
+        for (index, (instruction, checks)) in instructions.iter().enumerate() {
+            let this_result = self.process_instruction_inner(
+                index,
+                instruction,
+                &composite_result.resulting_accounts,
+                &fallback_accounts,
+                &self.compute_budget,
+                &program_runtime_environments,
+                &sysvar_cache,
+                None,
+                /* collect_resulting_accounts */ true,
+            );
+
+            let pass = this_result.run_checks_with_inputs(
+                checks,
+                config,
+                self,
+                &self.fund_payer(instruction, &composite_result.resulting_accounts),
+            );
+
+            if let Some(steps) = steps.as_deref_mut() {
+                steps.push((this_result.clone(), pass));
+            }
 
             composite_result.absorb(this_result);
 
-            if composite_result.program_result.is_err() {
+            if (stop_on_failed_checks && !pass) || composite_result.program_result.is_err() {
                 break;
             }
         }
@@ -1458,6 +2767,34 @@ impl Mollusk {
         composite_result
     }
 
+    /// Process a chain of instructions using the minified Solana Virtual
+    /// Machine (SVM) environment, then perform checks on each step, returning
+    /// every step's result alongside whether or not its checks passed.
+    ///
+    /// Unlike `process_and_validate_instruction_chain`, failing checks don't
+    /// panic. Execution stops after the first step whose program fails or
+    /// whose checks fail, so later steps are omitted from the output.
+    pub fn process_and_validate_instruction_chain_verbose(
+        &self,
+        instructions: &[(&Instruction, &[Check])],
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Vec<(InstructionResult, bool)> {
+        let config = Config {
+            panic: false,
+            ..self.config.clone()
+        };
+
+        let mut steps = Vec::with_capacity(instructions.len());
+        self.process_and_validate_instruction_chain_inner(
+            instructions,
+            accounts,
+            &config,
+            /* stop_on_failed_checks */ true,
+            Some(&mut steps),
+        );
+        steps
+    }
+
     /// Process multiple instructions using a single shared transaction context,
     /// then perform checks on the result. Panics if any checks fail.
     ///
@@ -1482,10 +2819,170 @@ impl Mollusk {
         checks: &[Check],
     ) -> TransactionResult {
         let result = self.process_transaction_instructions(instructions, accounts);
-        result.run_checks(checks, &self.config, self);
+        result.run_checks_with_inputs(checks, &self.config, self, accounts);
         result
     }
 
+    /// Find the minimum compute unit limit under which an instruction still
+    /// succeeds.
+    ///
+    /// Binary-searches the compute unit limit between the units consumed
+    /// under the configured budget and the configured limit itself. Returns
+    /// `None` if the instruction fails under the configured budget.
+    ///
+    /// Each attempt runs under a scratch copy of the compute budget, so the
+    /// harness's own budget is left untouched. The program runtime
+    /// environments and sysvar cache are built once and shared by every
+    /// attempt.
+    pub fn find_min_compute_units(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+    ) -> Option<u64> {
+        let result = self.process_instruction(instruction, accounts);
+        if result.program_result.is_err() {
+            return None;
+        }
+
+        let fallback_accounts = self.get_account_fallbacks(
+            std::iter::once(&instruction.program_id),
+            std::iter::once(instruction),
+            accounts,
+        );
+        let program_runtime_environments = self.program_runtime_environments();
+        let sysvar_cache = self.sysvars.setup_sysvar_cache(accounts);
+
+        let mut compute_budget = self.compute_budget;
+        let mut succeeds = |limit: u64| {
+            compute_budget.compute_unit_limit = limit;
+            self.process_instruction_inner(
+                0,
+                instruction,
+                accounts,
+                &fallback_accounts,
+                &compute_budget,
+                &program_runtime_environments,
+                &sysvar_cache,
+                None,
+                /* collect_resulting_accounts */ false,
+            )
+            .program_result
+            .is_ok()
+        };
+
+        // Invariant: The instruction succeeds with a limit of `high`.
+        let mut low = result.compute_units_consumed;
+        let mut high = self.compute_budget.compute_unit_limit;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if succeeds(mid) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        Some(high)
+    }
+
+    /// Assert that two program ELFs produce equivalent results for the same
+    /// instruction and accounts.
+    ///
+    /// Each program is loaded in turn under the instruction's program ID,
+    /// using the loader of the currently cached program (or the default
+    /// loader, if none is cached). The instruction is processed once per
+    /// program, and the two results are compared using the provided
+    /// `Compare` checks. Panics with the mismatching values on any
+    /// difference.
+    ///
+    /// The program cache is restored before comparing: the originally cached
+    /// program is put back, or the program is removed if none was cached.
+    pub fn assert_programs_equivalent(
+        &mut self,
+        program_a: &str,
+        program_b: &str,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[Compare],
+    ) {
+        let program_id = instruction.program_id;
+        let original_entry = self.program_cache.load_program(&program_id);
+        let original_elf = self.program_cache.get_program_elf_bytes(&program_id);
+        let loader_key = original_entry
+            .as_ref()
+            .map(|entry| entry.account_owner())
+            .unwrap_or(DEFAULT_LOADER_KEY);
+
+        self.add_program_with_loader(&program_id, program_a, &loader_key);
+        let result_a = self.process_instruction(instruction, accounts);
+
+        self.add_program_with_loader(&program_id, program_b, &loader_key);
+        let result_b = self.process_instruction(instruction, accounts);
+
+        match original_entry {
+            Some(entry) => {
+                self.program_cache
+                    .restore_entry(&program_id, entry, original_elf.as_deref())
+            }
+            None => {
+                self.program_cache.remove_program(&program_id);
+            }
+        }
+
+        result_a.compare_with_config(
+            &result_b,
+            checks,
+            &Config {
+                panic: true,
+                verbose: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Assert that an instruction is idempotent: running it a second time,
+    /// against its own resulting accounts, yields the same result as running
+    /// it once.
+    ///
+    /// The two results are compared using the provided `Compare` checks.
+    /// Panics with the mismatching values on any difference.
+    pub fn assert_idempotent(
+        &self,
+        instruction: &Instruction,
+        accounts: &[(Pubkey, AccountSharedData)],
+        checks: &[Compare],
+    ) {
+        let first = self.process_instruction(instruction, accounts);
+        let second = self.process_instruction(instruction, &first.resulting_accounts);
+
+        first.compare_with_config(
+            &second,
+            checks,
+            &Config {
+                panic: true,
+                verbose: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Create a `MolluskBatch` for processing many instructions in a loop.
+    ///
+    /// The batch builds the program runtime environments and sysvar cache
+    /// once, rather than on every call, which makes it considerably faster
+    /// for large workloads such as fixtures or fuzzing. Results are identical
+    /// to those of `process_instruction`.
+    ///
+    /// ```rust,ignore
+    /// let batch = mollusk.batch();
+    /// for (instruction, accounts) in cases {
+    ///     let result = batch.process_instruction(&instruction, &accounts);
+    /// }
+    /// ```
+    pub fn batch(&self) -> MolluskBatch<'_> {
+        MolluskBatch::new(self)
+    }
+
     /// Convert this `Mollusk` instance into a `MolluskContext` for stateful
     /// testing.
     ///
@@ -1496,18 +2993,19 @@ impl Mollusk {
     pub fn with_context<AS: AccountStore>(self, mut account_store: AS) -> MolluskContext<AS> {
         // For convenience, load all program accounts into the account store,
         // but only if they don't exist.
-        self.program_cache
+        let program_accounts = self
+            .program_cache
             .get_all_keyed_program_accounts()
             .into_iter()
-            .for_each(|(pubkey, account)| {
-                if account_store.get_account(&pubkey).is_none() {
-                    account_store.store_account(pubkey, account.into());
-                }
-            });
+            .filter(|(pubkey, _)| !account_store.contains(pubkey))
+            .map(|(pubkey, account)| (pubkey, account.into()))
+            .collect::<Vec<_>>();
+        account_store.store_accounts(program_accounts);
         MolluskContext {
             mollusk: self,
             account_store: Rc::new(RefCell::new(account_store)),
             hydrate_store: true, // <-- Default
+            persist_partial_chain: false,
         }
     }
 }
@@ -1533,6 +3031,9 @@ pub struct MolluskContext<AS: AccountStore> {
     pub mollusk: Mollusk,
     pub account_store: Rc<RefCell<AS>>,
     pub hydrate_store: bool,
+    /// When a step of an instruction chain fails, persist the changes made by
+    /// the steps before it, rather than discarding the entire chain.
+    pub persist_partial_chain: bool,
 }
 
 impl<AS: AccountStore> MolluskContext<AS> {
@@ -1587,14 +3088,61 @@ impl<AS: AccountStore> MolluskContext<AS> {
         accounts
     }
 
+    /// Fork the context into a new, independent context backed by a
+    /// `HashMap` account store.
+    ///
+    /// The fork starts with a copy of every account currently held by this
+    /// context's store, along with a clone of the underlying `Mollusk`
+    /// instance. From then on, the two contexts share no account state:
+    /// instructions processed by one are never visible to the other, and
+    /// programs added to one program cache are not added to the other. See
+    /// `Mollusk::clone` for what the two instances do share.
+    ///
+    /// Returns `None` if the account store can't enumerate its accounts. See
+    /// `AccountStore::all_accounts`, which is optional so that existing
+    /// stores, including ones backed by sources that can't be listed, such
+    /// as an RPC node, keep compiling without implementing it.
+    pub fn fork(&self) -> Option<MolluskContext<HashMap<Pubkey, AccountSharedData>>> {
+        let accounts = self.account_store.borrow().all_accounts()?;
+        Some(MolluskContext {
+            mollusk: self.mollusk.clone(),
+            account_store: Rc::new(RefCell::new(accounts.into_iter().collect())),
+            hydrate_store: self.hydrate_store,
+            persist_partial_chain: self.persist_partial_chain,
+        })
+    }
+
+    /// Modify the account at the given public key in the store, for example
+    /// to credit lamports from outside the program under test.
+    ///
+    /// See `AccountStore::update_account`.
+    pub fn update_account(&self, pubkey: &Pubkey, f: impl FnOnce(&mut AccountSharedData)) {
+        self.account_store.borrow_mut().update_account(pubkey, f);
+    }
+
     fn consume_mollusk_result(&self, result: &InstructionResult) {
         if result.program_result.is_ok() {
             // Only store resulting accounts if the result was success.
-            let mut store = self.account_store.borrow_mut();
-            for (pubkey, account) in result.resulting_accounts.iter() {
-                store.store_account(*pubkey, account.clone());
-            }
+            self.account_store
+                .borrow_mut()
+                .store_accounts(result.resulting_accounts.iter().cloned());
+        }
+    }
+
+    fn consume_mollusk_chain_result(&self, chain: ChainResult) -> InstructionResult {
+        // Each step's resulting accounts include the changes made by every
+        // step before it.
+        if let Some(last_success) = chain
+            .steps
+            .iter()
+            .rev()
+            .find(|step| step.program_result.is_ok())
+        {
+            self.account_store
+                .borrow_mut()
+                .store_accounts(last_success.resulting_accounts.iter().cloned());
         }
+        chain.composite
     }
 
     /// Process an instruction using the minified Solana Virtual Machine (SVM)
@@ -1608,8 +3156,24 @@ impl<AS: AccountStore> MolluskContext<AS> {
 
     /// Process a chain of instructions using the minified Solana Virtual
     /// Machine (SVM) environment.
+    ///
+    /// Accounts for every instruction in the chain are loaded from the store
+    /// up front. Changes made by each instruction, including accounts created
+    /// mid-chain, are carried forward to the next, and the store is only
+    /// updated once the entire chain succeeds.
+    ///
+    /// If `persist_partial_chain` is set, the changes made by every step
+    /// before a failing one are persisted instead.
     pub fn process_instruction_chain(&self, instructions: &[Instruction]) -> InstructionResult {
         let accounts = self.load_accounts_for_instructions(instructions.iter());
+
+        if self.persist_partial_chain {
+            let chain = self
+                .mollusk
+                .process_instruction_chain_retaining_steps(instructions, &accounts);
+            return self.consume_mollusk_chain_result(chain);
+        }
+
         let result = self
             .mollusk
             .process_instruction_chain(instructions, &accounts);
@@ -1634,6 +3198,11 @@ impl<AS: AccountStore> MolluskContext<AS> {
 
     /// Process a chain of instructions using the minified Solana Virtual
     /// Machine (SVM) environment, then perform checks on the result.
+    ///
+    /// As with `process_instruction_chain`, if `persist_partial_chain` is set,
+    /// the changes made by every step before a failing one are persisted.
+    /// Only program failures count here: when checks don't panic, a step
+    /// whose program succeeds but whose checks fail is still persisted.
     pub fn process_and_validate_instruction_chain(
         &self,
         instructions: &[(&Instruction, &[Check])],
@@ -1641,6 +3210,14 @@ impl<AS: AccountStore> MolluskContext<AS> {
         let accounts = self.load_accounts_for_instructions(
             instructions.iter().map(|(instruction, _)| *instruction),
         );
+
+        if self.persist_partial_chain {
+            let chain = self
+                .mollusk
+                .process_and_validate_instruction_chain_retaining_steps(instructions, &accounts);
+            return self.consume_mollusk_chain_result(chain);
+        }
+
         let result = self
             .mollusk
             .process_and_validate_instruction_chain(instructions, &accounts);
@@ -1648,3 +3225,1917 @@ impl<AS: AccountStore> MolluskContext<AS> {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        mollusk_svm_result::{ContextResult, ProgramResult},
+        solana_system_interface::instruction::transfer,
+    };
+
+    /// A funded sender and an empty recipient, both owned by the System
+    /// program.
+    fn transfer_accounts() -> (Pubkey, Pubkey, [(Pubkey, AccountSharedData); 2]) {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+        (sender, recipient, accounts)
+    }
+
+    #[test]
+    fn test_program_runtime_environments_cache() {
+        let mut mollusk = Mollusk::default();
+
+        let first = mollusk.program_runtime_environments();
+        let second = mollusk.program_runtime_environments();
+        assert!(Arc::ptr_eq(
+            &first.program_runtime_v1,
+            &second.program_runtime_v1
+        ));
+        assert!(Arc::ptr_eq(
+            &first.program_runtime_v2,
+            &second.program_runtime_v2
+        ));
+
+        // Changing the compute budget invalidates the cache.
+        mollusk.compute_budget.compute_unit_limit = 42;
+        let third = mollusk.program_runtime_environments();
+        assert!(!Arc::ptr_eq(
+            &first.program_runtime_v1,
+            &third.program_runtime_v1
+        ));
+
+        // Changing the feature set invalidates the cache.
+        mollusk
+            .feature_set
+            .deactivate(&agave_feature_set::disable_sbpf_v0_execution::id());
+        let fourth = mollusk.program_runtime_environments();
+        assert!(!Arc::ptr_eq(
+            &third.program_runtime_v1,
+            &fourth.program_runtime_v1
+        ));
+    }
+
+    #[test]
+    fn test_context_result() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let context = Mollusk::default().with_context(HashMap::from([
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ]));
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let result = context.process_instruction(&instruction);
+        let context_result = ContextResult::from(result.clone());
+
+        assert!(context_result.is_ok());
+        assert!(!context_result.is_err());
+        assert_eq!(
+            context_result.compute_units_consumed,
+            result.compute_units_consumed
+        );
+        assert!(context_result.compute_units_consumed > 0);
+        assert_eq!(
+            context_result.builtin_compute_units,
+            result.compute_units_consumed
+        );
+        assert_eq!(context_result.bpf_compute_units, 0);
+        assert_eq!(context_result.execution_time, result.execution_time);
+        assert_eq!(context_result.program_result, ProgramResult::Success);
+        assert_eq!(context_result.raw_result, Ok(()));
+        assert!(context_result.return_data.is_empty());
+
+        // A failed transfer.
+        let instruction = transfer(&sender, &recipient, 1_000_000);
+        let context_result = ContextResult::from(context.process_instruction(&instruction));
+        assert!(context_result.is_err());
+    }
+
+    #[test]
+    fn test_assert_idempotent() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        // Transferring nothing leaves the same state every time.
+        mollusk.assert_idempotent(
+            &transfer(&sender, &recipient, 0),
+            &accounts,
+            &[Compare::ProgramResult, Compare::all_resulting_accounts()],
+        );
+
+        // Each transfer moves more lamports.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mollusk.assert_idempotent(
+                &transfer(&sender, &recipient, 1_000),
+                &accounts,
+                &[Compare::ProgramResult, Compare::all_resulting_accounts()],
+            )
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_chain_composite_checks() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+        let step_compute_units = mollusk
+            .process_instruction(&transfer, &accounts)
+            .compute_units_consumed;
+
+        mollusk.process_and_validate_instruction_chain_with_composite_checks(
+            &[
+                (&transfer, &[Check::compute_units(step_compute_units)]),
+                (&transfer, &[Check::compute_units(step_compute_units)]),
+            ],
+            &accounts,
+            &[
+                Check::success(),
+                Check::compute_units(step_compute_units * 2),
+                Check::account(&recipient).lamports(2_000).build(),
+                Check::lamports_conserved(),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_chain_step_compute_units() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+        let result = mollusk.process_instruction_chain_retaining_steps(
+            &[transfer.clone(), transfer.clone(), transfer],
+            &accounts,
+        );
+
+        let step_compute_units = result.step_compute_units();
+        assert_eq!(step_compute_units.len(), 3);
+        assert!(step_compute_units.iter().all(|units| *units > 0));
+        assert_eq!(
+            step_compute_units.iter().sum::<u64>(),
+            result.composite.compute_units_consumed
+        );
+    }
+
+    #[test]
+    fn test_chain_result_step_checks() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+        let result = mollusk.process_and_validate_instruction_chain_retaining_steps(
+            &[
+                (&transfer, &[Check::success()]),
+                (&transfer, &[Check::success()]),
+                (&transfer, &[Check::success()]),
+            ],
+            &accounts,
+        );
+
+        assert_eq!(result.steps.len(), 3);
+        assert_eq!(result.composite, {
+            let mut composite = InstructionResult {
+                resulting_accounts: accounts.to_vec(),
+                ..Default::default()
+            };
+            result
+                .steps
+                .iter()
+                .for_each(|step| composite.absorb(step.clone()));
+            composite
+        });
+
+        // Extra checks against the second step, without re-executing.
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        assert!(result.step(1).run_checks(
+            &[
+                Check::account(&sender).lamports(8_000).build(),
+                Check::account(&recipient).lamports(2_000).build(),
+            ],
+            &config,
+            &mollusk,
+        ));
+        assert!(!result.step(1).run_checks(
+            &[Check::account(&recipient).lamports(3_000).build()],
+            &config,
+            &mollusk,
+        ));
+    }
+
+    #[cfg(feature = "no-default-logger")]
+    #[test]
+    fn test_no_default_logger() {
+        use std::sync::Mutex;
+
+        struct RecordingLogger(Mutex<Vec<String>>);
+
+        impl log::Log for RecordingLogger {
+            fn enabled(&self, _metadata: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: RecordingLogger = RecordingLogger(Mutex::new(Vec::new()));
+
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let _mollusk = Mollusk::default();
+
+        // Neither the logger nor its level were replaced.
+        assert_eq!(log::max_level(), log::LevelFilter::Warn);
+        log::warn!("still recording");
+        assert!(LOGGER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|message| message == "still recording"));
+    }
+
+    #[test]
+    fn test_process_instruction_capturing_context() {
+        const SENDER: Pubkey = Pubkey::new_from_array([1; 32]);
+        const RECIPIENT: Pubkey = Pubkey::new_from_array([2; 32]);
+
+        // A builtin which transfers from the sender to the recipient through
+        // a CPI to the System program.
+        solana_program_runtime::declare_process_instruction!(TransferCpi, 0, |invoke_context| {
+            invoke_context.native_invoke(transfer(&SENDER, &RECIPIENT, 1_000).into(), &[])
+        });
+
+        let mut mollusk = Mollusk::default();
+        let program_id = Pubkey::new_unique();
+        mollusk.insert_program_cache_entry(
+            &program_id,
+            Arc::new(ProgramCacheEntry::new_builtin(0, 0, TransferCpi::vm)),
+        );
+
+        let (system_program, system_program_account) =
+            crate::program::keyed_account_for_system_program();
+        let accounts = [
+            (
+                SENDER,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                RECIPIENT,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (system_program, system_program_account.into()),
+        ];
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(SENDER, true),
+                AccountMeta::new(RECIPIENT, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        );
+
+        let (result, captured) =
+            mollusk.process_instruction_capturing_context(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+
+        let find = |key: &Pubkey| {
+            captured
+                .iter()
+                .find(|(captured_key, _)| captured_key == key)
+                .map(|(_, account)| account)
+        };
+
+        // The accounts modified by the CPI.
+        assert_eq!(find(&SENDER).unwrap().lamports(), 9_000);
+        assert_eq!(find(&RECIPIENT).unwrap().lamports(), 1_000);
+
+        // The stubbed program account and instructions sysvar are also
+        // captured.
+        assert!(find(&program_id).unwrap().executable());
+        assert!(find(&solana_instructions_sysvar::ID).is_some());
+        assert_eq!(captured.len(), 5);
+    }
+
+    #[test]
+    fn test_set_blockhash() {
+        const PROGRAM_ID: Pubkey = Pubkey::new_from_array([1; 32]);
+
+        // A builtin which returns the blockhash and lamports per signature it
+        // observes.
+        solana_program_runtime::declare_process_instruction!(
+            ReturnBlockhash,
+            0,
+            |invoke_context| {
+                let environment_config = &invoke_context.environment_config;
+                let mut data = environment_config.blockhash.to_bytes().to_vec();
+                data.extend_from_slice(
+                    &environment_config
+                        .blockhash_lamports_per_signature
+                        .to_le_bytes(),
+                );
+                invoke_context
+                    .transaction_context
+                    .set_return_data(PROGRAM_ID, data)
+            }
+        );
+
+        let mut mollusk = Mollusk::default();
+        mollusk.insert_program_cache_entry(
+            &PROGRAM_ID,
+            Arc::new(ProgramCacheEntry::new_builtin(0, 0, ReturnBlockhash::vm)),
+        );
+
+        let instruction = Instruction::new_with_bytes(PROGRAM_ID, &[], vec![]);
+        let observed = |mollusk: &Mollusk| {
+            let result = mollusk.process_instruction(&instruction, &[]);
+            assert!(result.program_result.is_ok());
+            let (blockhash, lamports_per_signature) = result.return_data.split_at(32);
+            (
+                Hash::new_from_array(blockhash.try_into().unwrap()),
+                u64::from_le_bytes(lamports_per_signature.try_into().unwrap()),
+            )
+        };
+
+        assert_eq!(observed(&mollusk), (Hash::default(), 5000));
+
+        let blockhash = Hash::new_from_array([7; 32]);
+        mollusk.set_blockhash(blockhash);
+        mollusk.set_lamports_per_signature(10_000);
+        assert_eq!(observed(&mollusk), (blockhash, 10_000));
+    }
+
+    #[test]
+    fn test_add_programs() {
+        let mut mollusk = Mollusk::default();
+
+        // Resolved relative to the current working directory.
+        let programs = [
+            (Pubkey::new_unique(), "../programs/memo/src/elf/memo"),
+            (Pubkey::new_unique(), "../programs/token/src/elf/token"),
+            (
+                Pubkey::new_unique(),
+                "../programs/token/src/elf/associated_token",
+            ),
+        ];
+        let program_ids = programs.map(|(program_id, _)| program_id);
+
+        let cached_before = mollusk.program_cache.cached_program_ids();
+        assert!(program_ids
+            .iter()
+            .all(|program_id| !cached_before.contains(program_id)));
+
+        mollusk.add_programs(&programs);
+
+        let cached_after = mollusk.program_cache.cached_program_ids();
+        assert_eq!(cached_after.len(), cached_before.len() + 3);
+        for (program_id, program_name) in programs {
+            assert!(cached_after.contains(&program_id));
+            assert_eq!(
+                mollusk
+                    .program_cache
+                    .load_program(&program_id)
+                    .unwrap()
+                    .account_owner(),
+                DEFAULT_LOADER_KEY,
+            );
+            assert_eq!(
+                mollusk.program_cache.get_program_elf_bytes(&program_id),
+                Some(file::load_program_elf(program_name)),
+            );
+        }
+
+        let memo_v2 = Pubkey::new_unique();
+        mollusk.add_programs_with_loader(
+            &[(memo_v2, "../programs/memo/src/elf/memo")],
+            &crate::program::loader_keys::LOADER_V2,
+        );
+        assert_eq!(
+            mollusk
+                .program_cache
+                .load_program(&memo_v2)
+                .unwrap()
+                .account_owner(),
+            crate::program::loader_keys::LOADER_V2,
+        );
+    }
+
+    #[test]
+    fn test_process_instruction_raw() {
+        let mollusk = Mollusk::default();
+
+        let sender = Pubkey::new_unique();
+        let (system_program, system_program_account) =
+            crate::program::keyed_account_for_system_program();
+
+        // A self-transfer, with the sender's account referenced by two
+        // instruction accounts.
+        let instruction = transfer(&sender, &sender, 1_000);
+        let transaction_accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (system_program, system_program_account.into()),
+        ];
+        let instruction_accounts = [
+            InstructionAccount::new(
+                /* index_in_transaction */ 0, /* is_signer */ true,
+                /* is_writable */ true,
+            ),
+            InstructionAccount::new(
+                /* index_in_transaction */ 0, /* is_signer */ false,
+                /* is_writable */ true,
+            ),
+        ];
+
+        let result = mollusk.process_instruction_raw(
+            &instruction,
+            /* program_id_index */ 1,
+            &instruction_accounts,
+            &transaction_accounts,
+        );
+        assert!(result.program_result.is_ok());
+        assert_eq!(result.resulting_accounts.len(), 2);
+        assert_eq!(result.resulting_accounts[0].1.lamports(), 10_000);
+
+        // Matches the deduplicated accounts compiled by `process_instruction`.
+        let expected = mollusk.process_instruction(&instruction, &transaction_accounts[..1]);
+        assert_eq!(result.raw_result, expected.raw_result);
+        assert_eq!(
+            result.compute_units_consumed,
+            expected.compute_units_consumed
+        );
+        assert_eq!(
+            result.resulting_accounts[..1],
+            expected.resulting_accounts[..]
+        );
+
+        // Privileges are taken as-is: without a signer, the transfer fails.
+        let result = mollusk.process_instruction_raw(
+            &instruction,
+            /* program_id_index */ 1,
+            &[instruction_accounts[1], instruction_accounts[1]],
+            &transaction_accounts,
+        );
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_account_data_size_limit() {
+        let mut mollusk = Mollusk::default();
+        mollusk.set_account_data_size_limit(100);
+
+        let key = Pubkey::new_unique();
+        let accounts = [(
+            key,
+            AccountSharedData::new(10_000_000, 0, &solana_system_program::id()),
+        )];
+
+        mollusk.process_and_validate_instruction(
+            &solana_system_interface::instruction::allocate(&key, 100),
+            &accounts,
+            &[Check::success(), Check::account(&key).space(100).build()],
+        );
+
+        mollusk.process_and_validate_instruction(
+            &solana_system_interface::instruction::allocate(&key, 101),
+            &accounts,
+            &[
+                Check::instruction_err(InstructionError::MaxAccountsDataAllocationsExceeded),
+                Check::account(&key).space(0).build(),
+            ],
+        );
+
+        // Growth accumulates across the instructions of a transaction.
+        let other_key = Pubkey::new_unique();
+        let result = mollusk.process_transaction_instructions(
+            &[
+                solana_system_interface::instruction::allocate(&key, 60),
+                solana_system_interface::instruction::allocate(&other_key, 60),
+            ],
+            &[
+                accounts[0].clone(),
+                (
+                    other_key,
+                    AccountSharedData::new(10_000_000, 0, &solana_system_program::id()),
+                ),
+            ],
+        );
+        assert_eq!(
+            result.raw_result,
+            Err(TransactionError::InstructionError(
+                1,
+                InstructionError::MaxAccountsDataAllocationsExceeded
+            ))
+        );
+    }
+
+    #[test]
+    fn test_assert_programs_equivalent() {
+        let mut mollusk = Mollusk::default();
+
+        let memo_elf = include_bytes!("../../programs/memo/src/elf/memo.so");
+        let memo_program = Pubkey::new_unique();
+        mollusk.add_program_with_loader_and_elf(
+            &memo_program,
+            &crate::program::loader_keys::LOADER_V2,
+            memo_elf,
+        );
+        let original_entry = mollusk.program_cache.load_program(&memo_program).unwrap();
+
+        // Resolved relative to the current working directory.
+        let memo_path = "../programs/memo/src/elf/memo";
+        let checks = [
+            Compare::ComputeUnits,
+            Compare::ProgramResult,
+            Compare::ReturnData,
+        ];
+
+        mollusk.assert_programs_equivalent(
+            memo_path,
+            memo_path,
+            &Instruction::new_with_bytes(memo_program, b"mollusk", vec![]),
+            &[],
+            &checks,
+        );
+
+        // The original entry is restored.
+        let entry = mollusk.program_cache.load_program(&memo_program).unwrap();
+        assert!(Arc::ptr_eq(&entry, &original_entry));
+        assert_eq!(
+            mollusk.program_cache.get_program_elf_bytes(&memo_program),
+            Some(memo_elf.to_vec()),
+        );
+
+        // A program which wasn't cached is removed again.
+        let uncached_program = Pubkey::new_unique();
+        mollusk.assert_programs_equivalent(
+            memo_path,
+            memo_path,
+            &Instruction::new_with_bytes(uncached_program, b"mollusk", vec![]),
+            &[],
+            &checks,
+        );
+        assert!(mollusk
+            .program_cache
+            .load_program(&uncached_program)
+            .is_none());
+    }
+
+    #[test]
+    fn test_process_instruction_shared_accounts() {
+        let mollusk = Mollusk::default();
+
+        let key = Pubkey::new_unique();
+        let instruction = solana_system_interface::instruction::allocate(&key, 64);
+
+        let owned_accounts = [(
+            key,
+            AccountSharedData::from(Account::new(10_000, 0, &solana_system_program::id())),
+        )];
+        let owned_result = mollusk.process_instruction(&instruction, &owned_accounts);
+
+        // As if loaded from a snapshot, with the caller keeping a handle.
+        let shared_account = AccountSharedData::new(10_000, 0, &solana_system_program::id());
+        let shared_accounts = [(key, shared_account.clone())];
+        let shared_result = mollusk.process_instruction(&instruction, &shared_accounts);
+
+        owned_result.compare(&shared_result);
+        assert_eq!(shared_result.resulting_accounts[0].1.data().len(), 64);
+
+        // The caller's account is untouched.
+        assert_eq!(shared_account.data().len(), 0);
+        assert_eq!(shared_accounts[0].1, shared_account);
+    }
+
+    #[test]
+    fn test_process_and_validate_instruction_chain_verbose() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = |lamports| {
+            solana_system_interface::instruction::transfer(&sender, &recipient, lamports)
+        };
+        let (first, second, third) = (transfer(1_000), transfer(2_000), transfer(3_000));
+
+        // The second step's checks fail, so the third step never runs.
+        let steps = mollusk.process_and_validate_instruction_chain_verbose(
+            &[
+                (
+                    &first,
+                    &[
+                        Check::success(),
+                        Check::account(&recipient).lamports(1_000).build(),
+                    ],
+                ),
+                (
+                    &second,
+                    &[
+                        Check::success(),
+                        Check::account(&recipient).lamports(1).build(),
+                    ],
+                ),
+                (&third, &[Check::success()]),
+            ],
+            &accounts,
+        );
+
+        assert_eq!(steps.len(), 2);
+        assert!(steps[0].1);
+        assert!(!steps[1].1);
+        assert!(steps[1].0.program_result.is_ok());
+        assert_eq!(
+            steps[1].0.get_account(&recipient).unwrap().lamports(),
+            3_000
+        );
+
+        // Likewise when the second step's program fails.
+        let steps = mollusk.process_and_validate_instruction_chain_verbose(
+            &[
+                (&first, &[Check::success()]),
+                (&transfer(20_000), &[Check::success()]),
+                (&third, &[Check::success()]),
+            ],
+            &accounts,
+        );
+
+        assert_eq!(steps.len(), 2);
+        assert!(steps[0].1);
+        assert!(!steps[1].1);
+        assert!(steps[1].0.program_result.is_err());
+
+        // Without panicking, the other chain APIs keep going after failed
+        // checks.
+        let mut mollusk = mollusk;
+        mollusk.config.panic = false;
+        let chain = mollusk.process_and_validate_instruction_chain_retaining_steps(
+            &[
+                (&first, &[Check::account(&recipient).lamports(1).build()]),
+                (&second, &[Check::success()]),
+                (&third, &[Check::success()]),
+            ],
+            &accounts,
+        );
+
+        assert_eq!(chain.steps.len(), 3);
+        assert_eq!(
+            chain.composite.get_account(&recipient).unwrap().lamports(),
+            6_000
+        );
+    }
+
+    #[test]
+    fn test_capture_accounts_on_failure() {
+        let mut mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        // The first transfer succeeds, the second fails.
+        let instructions = [
+            transfer(&sender, &recipient, 1_000),
+            transfer(&sender, &recipient, 20_000),
+        ];
+
+        // By default, the failed transaction's account changes are discarded.
+        let result = mollusk.process_transaction_instructions(&instructions, &accounts);
+        assert!(result.program_result.is_err());
+        assert_eq!(result.resulting_accounts, accounts);
+
+        mollusk.config.capture_accounts_on_failure = true;
+
+        let result = mollusk.process_transaction_instructions(&instructions, &accounts);
+        assert!(result.program_result.is_err());
+        assert_eq!(result.resulting_accounts[0].1.lamports(), 9_000);
+        assert_eq!(result.resulting_accounts[1].1.lamports(), 1_000);
+    }
+
+    #[test]
+    fn test_find_min_compute_units() {
+        let mut mollusk = Mollusk::default();
+
+        let memo_program = Pubkey::new_unique();
+        mollusk.add_program_with_loader_and_elf(
+            &memo_program,
+            &crate::program::loader_keys::LOADER_V2,
+            include_bytes!("../../programs/memo/src/elf/memo.so"),
+        );
+        let instruction = Instruction::new_with_bytes(memo_program, b"mollusk", vec![]);
+
+        let consumed = mollusk
+            .process_instruction(&instruction, &[])
+            .compute_units_consumed;
+        let compute_budget = mollusk.compute_budget;
+        let min = mollusk.find_min_compute_units(&instruction, &[]).unwrap();
+        assert!(min >= consumed);
+        assert!(min < compute_budget.compute_unit_limit);
+
+        // The harness's own budget is untouched.
+        assert_eq!(mollusk.compute_budget, compute_budget);
+
+        mollusk.compute_budget.compute_unit_limit = min;
+        assert!(mollusk
+            .process_instruction(&instruction, &[])
+            .program_result
+            .is_ok());
+
+        mollusk.compute_budget.compute_unit_limit = min - 1;
+        assert!(mollusk
+            .process_instruction(&instruction, &[])
+            .program_result
+            .is_err());
+
+        // A failing instruction has no minimum.
+        mollusk.compute_budget = compute_budget;
+        let (sender, recipient, accounts) = transfer_accounts();
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 20_000);
+        assert_eq!(mollusk.find_min_compute_units(&transfer, &accounts), None);
+    }
+
+    #[test]
+    fn test_warn_on_duplicate_accounts() {
+        let mut mollusk = Mollusk::default();
+        mollusk.config.warn_on_duplicate_accounts = true;
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (
+                sender,
+                AccountSharedData::new(5_000, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        assert_eq!(Mollusk::duplicate_account_keys(&accounts), vec![&sender]);
+        assert!(Mollusk::duplicate_account_keys(&accounts[..2]).is_empty());
+
+        // The warning doesn't change the outcome: the first occurrence is
+        // used.
+        let result = mollusk.process_instruction(&transfer(&sender, &recipient, 8_000), &accounts);
+        assert!(result.program_result.is_ok());
+        assert_eq!(result.get_account(&recipient).unwrap().lamports(), 8_000);
+    }
+
+    #[test]
+    fn test_validate_instruction_privileges() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+        assert_eq!(
+            mollusk.validate_instruction_privileges(&transfer, &accounts),
+            Ok(())
+        );
+
+        // The recipient is missing.
+        let err = mollusk
+            .validate_instruction_privileges(&transfer, &accounts[..1])
+            .unwrap_err();
+        assert!(err.contains(&recipient.to_string()));
+        assert!(err.contains("was not provided"));
+
+        // The program is marked writable.
+        let mut writable_program = transfer.clone();
+        writable_program
+            .accounts
+            .push(AccountMeta::new(solana_system_program::id(), false));
+        let err = mollusk
+            .validate_instruction_privileges(&writable_program, &accounts)
+            .unwrap_err();
+        assert!(err.contains("demoted to readonly"));
+
+        // The program isn't cached.
+        let unknown = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]);
+        let err = mollusk
+            .validate_instruction_privileges(&unknown, &accounts)
+            .unwrap_err();
+        assert!(err.contains("not in the program cache"));
+    }
+
+    #[test]
+    fn test_validate_cpi_privileges() {
+        const SENDER: Pubkey = Pubkey::new_from_array([1; 32]);
+        const RECIPIENT: Pubkey = Pubkey::new_from_array([2; 32]);
+
+        // A builtin which transfers from the sender to the recipient through
+        // a CPI to the System program, which needs both to be writable.
+        solana_program_runtime::declare_process_instruction!(TransferCpi, 0, |invoke_context| {
+            invoke_context.native_invoke(transfer(&SENDER, &RECIPIENT, 1_000).into(), &[])
+        });
+
+        let mut mollusk = Mollusk::default();
+        let program_id = Pubkey::new_unique();
+        mollusk.insert_program_cache_entry(
+            &program_id,
+            Arc::new(ProgramCacheEntry::new_builtin(0, 0, TransferCpi::vm)),
+        );
+
+        let (system_program, system_program_account) =
+            crate::program::keyed_account_for_system_program();
+        let accounts = [
+            (
+                SENDER,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                RECIPIENT,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (system_program, system_program_account.into()),
+        ];
+        let caller = |recipient_meta: AccountMeta| {
+            Instruction::new_with_bytes(
+                program_id,
+                &[],
+                vec![
+                    AccountMeta::new(SENDER, true),
+                    recipient_meta,
+                    AccountMeta::new_readonly(system_program, false),
+                ],
+            )
+        };
+        let cpi = transfer(&SENDER, &RECIPIENT, 1_000);
+
+        // The caller only has the recipient as readonly, so the CPI escalates
+        // its privileges.
+        let escalating = caller(AccountMeta::new_readonly(RECIPIENT, false));
+        let err = Mollusk::validate_cpi_privileges(&escalating, &cpi).unwrap_err();
+        assert!(err.contains(&RECIPIENT.to_string()));
+        assert!(err.contains("readonly in the calling instruction"));
+
+        let result = mollusk.process_instruction(&escalating, &accounts);
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::PrivilegeEscalation)
+        );
+
+        // With the recipient writable, both agree the CPI is fine.
+        let granting = caller(AccountMeta::new(RECIPIENT, false));
+        assert_eq!(Mollusk::validate_cpi_privileges(&granting, &cpi), Ok(()));
+        let result = mollusk.process_instruction(&granting, &accounts);
+        assert!(result.program_result.is_ok());
+
+        // An account the caller was never given.
+        let stranger = Pubkey::new_unique();
+        let cpi = transfer(&SENDER, &stranger, 1_000);
+        let err = Mollusk::validate_cpi_privileges(&granting, &cpi).unwrap_err();
+        assert!(err.contains("was not provided to the caller"));
+    }
+
+    #[test]
+    fn test_instruction_with_pda_signer() {
+        let mollusk = Mollusk::default();
+
+        let owning_program = Pubkey::new_unique();
+        let (pda, bump) = Pubkey::find_program_address(&[b"vault"], &owning_program);
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                pda,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        // The System program requires the sender to sign.
+        let mut transfer = solana_system_interface::instruction::transfer(&pda, &recipient, 1_000);
+        transfer.accounts[0].is_signer = false;
+        let result = mollusk.process_instruction(&transfer, &accounts);
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::MissingRequiredSignature)
+        );
+
+        let transfer =
+            Mollusk::instruction_with_pda_signer(transfer, &[b"vault", &[bump]], &owning_program);
+        assert!(transfer.accounts[0].is_signer);
+        let result = mollusk.process_instruction(&transfer, &accounts);
+        assert!(result.program_result.is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "but the program is cached under loader")]
+    fn test_program_loader_mismatch() {
+        let mollusk = Mollusk::default();
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instruction = transfer(&sender, &recipient, 1);
+
+        // The System program is a builtin, but the account claims it was
+        // deployed with BPF Loader v2.
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (
+                solana_system_program::id(),
+                crate::program::create_program_account_loader_v2(&[]).into(),
+            ),
+        ];
+
+        mollusk.process_instruction(&instruction, &accounts);
+    }
+
+    #[test]
+    fn test_with_active_features() {
+        let features = [
+            agave_feature_set::enable_sbpf_v1_deployment_and_execution::id(),
+            agave_feature_set::enable_sbpf_v2_deployment_and_execution::id(),
+        ];
+        let mollusk = Mollusk::with_active_features(&features);
+
+        assert_eq!(mollusk.feature_set.active().len(), features.len());
+        features.iter().for_each(|feature_id| {
+            assert!(mollusk.feature_set.is_active(feature_id));
+            assert_eq!(mollusk.feature_set.activated_slot(feature_id), Some(0));
+        });
+        assert!(!mollusk
+            .feature_set
+            .is_active(&agave_feature_set::disable_sbpf_v0_execution::id()));
+    }
+
+    #[test]
+    fn test_process_instruction_chain_with_budgets() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+
+        let result = mollusk.process_instruction_chain_with_budgets(
+            &[(&transfer, 200_000), (&transfer, 200_000)],
+            &accounts,
+        );
+        assert!(result.program_result.is_ok());
+        let per_step_units = result.compute_units_consumed / 2;
+
+        // The second step doesn't get enough compute units.
+        let result = mollusk.process_instruction_chain_with_budgets(
+            &[
+                (&transfer, 200_000),
+                (&transfer, per_step_units - 1),
+                (&transfer, 200_000),
+            ],
+            &accounts,
+        );
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::ComputationalBudgetExceeded)
+        );
+        // The first step's transfer is retained, the third never ran.
+        assert_eq!(result.get_account(&recipient).unwrap().lamports(), 1_000);
+
+        // The harness's budget is untouched.
+        assert_eq!(
+            mollusk.compute_budget.compute_unit_limit,
+            Mollusk::default().compute_budget.compute_unit_limit
+        );
+    }
+
+    fn versioned_transfer_message(
+        sender: &Pubkey,
+        recipient: &Pubkey,
+        lookup_table: &AddressLookupTableAccount,
+    ) -> VersionedMessage {
+        let instruction = solana_system_interface::instruction::transfer(sender, recipient, 1_000);
+        VersionedMessage::V0(
+            solana_message::v0::Message::try_compile(
+                sender,
+                &[instruction],
+                std::slice::from_ref(lookup_table),
+                Hash::default(),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_process_versioned_message() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), recipient],
+        };
+        let message = versioned_transfer_message(&sender, &recipient, &lookup_table);
+        // The recipient is only reachable through the lookup table.
+        assert!(!message.static_account_keys().contains(&recipient));
+
+        let result = mollusk.process_versioned_message(&message, &[lookup_table], &accounts);
+        assert!(result.program_result.is_ok());
+        assert_eq!(result.get_account(&sender).unwrap().lamports(), 9_000);
+        assert_eq!(result.get_account(&recipient).unwrap().lamports(), 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Address lookup table required by the message was not provided")]
+    fn test_process_versioned_message_lookup_table_missing() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![recipient],
+        };
+        let message = versioned_transfer_message(&sender, &recipient, &lookup_table);
+
+        Mollusk::default().process_versioned_message(&message, &[], &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Address lookup table index 1 is out of bounds")]
+    fn test_process_versioned_message_lookup_index_out_of_bounds() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique(), recipient],
+        };
+        let message = versioned_transfer_message(&sender, &recipient, &lookup_table);
+
+        // The provided table no longer holds the recipient's index.
+        let truncated = AddressLookupTableAccount {
+            key: lookup_table.key,
+            addresses: vec![lookup_table.addresses[0]],
+        };
+        Mollusk::default().process_versioned_message(&message, &[truncated], &[]);
+    }
+
+    #[test]
+    fn test_program_not_cached() {
+        let mollusk = Mollusk::default();
+
+        let missing_program = Pubkey::new_unique();
+        assert!(!mollusk.is_program_cached(&missing_program));
+        assert!(mollusk.is_program_cached(&solana_system_program::id()));
+
+        let instruction = Instruction::new_with_bytes(missing_program, &[], vec![]);
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            mollusk.process_instruction(&instruction, &[])
+        }))
+        .unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+
+        assert!(message.contains(&format!(
+            "Program targeted by the instruction is missing from the cache: {missing_program}"
+        )));
+        assert!(message.contains(&solana_system_program::id().to_string()));
+        assert!(message.contains(&crate::program::loader_keys::LOADER_V3.to_string()));
+    }
+
+    #[test]
+    fn test_process_instructions_independent() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let amounts = [1_000, 2_000, 3_000];
+        let instructions = amounts
+            .iter()
+            .map(|amount| transfer(&sender, &recipient, *amount))
+            .collect::<Vec<_>>();
+
+        let results = mollusk.process_instructions_independent(&instructions, &accounts);
+        assert_eq!(results.len(), amounts.len());
+
+        // Each result reflects only its own transfer from the original state.
+        results.iter().zip(amounts).for_each(|(result, amount)| {
+            assert!(result.program_result.is_ok());
+            assert_eq!(
+                result.get_account(&sender).unwrap().lamports(),
+                10_000 - amount
+            );
+            assert_eq!(result.get_account(&recipient).unwrap().lamports(), amount);
+        });
+    }
+
+    #[test]
+    fn test_builtin_and_bpf_compute_units() {
+        let mut mollusk = Mollusk::default();
+
+        let memo_program = Pubkey::new_unique();
+        mollusk.add_program_with_loader_and_elf(
+            &memo_program,
+            &crate::program::loader_keys::LOADER_V2,
+            include_bytes!("../../programs/memo/src/elf/memo.so"),
+        );
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let instructions = [
+            transfer(&sender, &recipient, 1_000),
+            Instruction::new_with_bytes(memo_program, b"mollusk", vec![]),
+        ];
+        let result = mollusk.process_instruction_chain(&instructions, &accounts);
+
+        assert!(result.program_result.is_ok());
+        assert!(result.builtin_compute_units > 0);
+        assert!(result.bpf_compute_units > 0);
+        assert_eq!(
+            result.builtin_compute_units + result.bpf_compute_units,
+            result.compute_units_consumed
+        );
+    }
+
+    #[test]
+    fn test_add_noop_program() {
+        let mut mollusk = Mollusk::default();
+
+        let program_id = Pubkey::new_unique();
+        mollusk.add_noop_program(&program_id);
+
+        let instruction = Instruction::new_with_bytes(program_id, &[], vec![]);
+        let result = mollusk.process_and_validate_instruction(
+            &instruction,
+            &[],
+            &[Check::success(), Check::consumed_compute_units()],
+        );
+
+        // Just the program's two instructions.
+        assert!(result.compute_units_consumed <= 2);
+    }
+
+    #[test]
+    fn test_program_account_rent_epoch() {
+        let mut mollusk = Mollusk::default();
+
+        let memo_program = Pubkey::new_unique();
+        let elf = include_bytes!("../../programs/memo/src/elf/memo.so");
+        mollusk.add_program_with_loader_and_elf(
+            &memo_program,
+            &crate::program::loader_keys::LOADER_V2,
+            elf,
+        );
+
+        let program_account = crate::program::with_rent_epoch(
+            crate::program::create_program_account_loader_v2(elf),
+            u64::MAX,
+        );
+        let accounts = [(memo_program, program_account.into())];
+
+        let instruction = Instruction::new_with_bytes(memo_program, b"mollusk", vec![]);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+
+        assert!(result.program_result.is_ok());
+        assert_eq!(
+            result.get_account(&memo_program).unwrap().rent_epoch(),
+            u64::MAX
+        );
+    }
+
+    #[cfg(feature = "precompiles")]
+    #[test]
+    fn test_process_instruction_with_features() {
+        let mollusk = Mollusk::default();
+        let feature_id = agave_feature_set::enable_secp256r1_precompile::id();
+
+        let instruction = Instruction::new_with_bytes(
+            crate::program::precompile_keys::SECP256R1_PROGRAM,
+            &[0, 0],
+            vec![],
+        );
+
+        let enabled =
+            mollusk.process_instruction_with_features(&instruction, &[], &[(feature_id, true)]);
+        let disabled =
+            mollusk.process_instruction_with_features(&instruction, &[], &[(feature_id, false)]);
+
+        // Without the feature, the program isn't a precompile at all.
+        assert!(disabled.program_result.is_err());
+        assert_ne!(enabled.program_result, disabled.program_result);
+
+        // The harness's own feature set is untouched.
+        assert!(mollusk.feature_set.is_active(&feature_id));
+    }
+
+    #[test]
+    fn test_process_instruction_with_features_reloads_programs() {
+        let program_id = Pubkey::new_unique();
+        let mut mollusk = Mollusk::default();
+        mollusk.add_program_with_loader_and_elf(
+            &program_id,
+            &crate::program::loader_keys::LOADER_V2,
+            include_bytes!("../../programs/memo/src/elf/memo.so"),
+        );
+        let feature_id = agave_feature_set::disable_sbpf_v0_execution::id();
+
+        let instruction = Instruction::new_with_bytes(program_id, b"memo", vec![]);
+
+        // SBPF v0 programs fail verification once the feature is active.
+        let result =
+            mollusk.process_instruction_with_features(&instruction, &[], &[(feature_id, true)]);
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(InstructionError::UnsupportedProgramId),
+        );
+
+        // The harness's own program cache is untouched.
+        let result = mollusk.process_instruction(&instruction, &[]);
+        assert!(result.program_result.is_ok());
+    }
+
+    #[test]
+    fn test_compute_units_by_feature() {
+        let program_id = Pubkey::new_unique();
+        let mut mollusk = Mollusk::default();
+        mollusk.add_program_with_loader_and_elf(
+            &program_id,
+            &crate::program::loader_keys::LOADER_V2,
+            include_bytes!("../../programs/memo/src/elf/memo.so"),
+        );
+        let feature_id = agave_feature_set::disable_sbpf_v0_execution::id();
+
+        let instruction = Instruction::new_with_bytes(program_id, b"memo", vec![]);
+        let (off, on) = mollusk.compute_units_by_feature(&instruction, &[], feature_id);
+
+        // With the feature off, the memo program runs as usual.
+        let expected = mollusk
+            .process_instruction(&instruction, &[])
+            .compute_units_consumed;
+        assert!(expected > 0);
+        assert_eq!(off, expected);
+
+        // With the feature on, the SBPF v0 program fails verification and
+        // never executes.
+        assert!(on < off);
+
+        // The harness's own feature set is untouched.
+        assert!(!mollusk.feature_set.is_active(&feature_id));
+    }
+
+    #[test]
+    fn test_process_instruction_chain_conserving() {
+        let mollusk = Mollusk::default();
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let sink = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (
+                sink,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        let result = mollusk.process_instruction_chain_conserving(
+            &[
+                transfer(&sender, &recipient, 1_000),
+                transfer(&sender, &sink, 500),
+            ],
+            &accounts,
+            &[sink],
+        );
+        assert!(result.program_result.is_ok());
+        assert_eq!(result.get_account(&sink).unwrap().lamports(), 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Lamports were not conserved after step 1")]
+    fn test_process_instruction_chain_conserving_leak() {
+        // Auto-funding mints lamports out of thin air, which breaks
+        // conservation.
+        let mut mollusk = Mollusk::default();
+        mollusk.config.auto_fund_payer = Some(10_000);
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        mollusk.process_instruction_chain_conserving(
+            &[instruction.clone(), instruction],
+            &accounts,
+            &[],
+        );
+    }
+
+    #[cfg(feature = "detailed-timings")]
+    #[test]
+    fn test_detailed_timings() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+
+        let timings = result.timings.as_ref().unwrap();
+        assert_eq!(timings.details.execute_us.0, result.execution_time);
+    }
+
+    #[test]
+    fn test_collect_resulting_accounts() {
+        let mut mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let collected = mollusk.process_instruction(&instruction, &accounts);
+
+        mollusk.config.collect_resulting_accounts = false;
+        let uncollected = mollusk.process_instruction(&instruction, &accounts);
+
+        assert_eq!(collected.resulting_accounts.len(), 2);
+        assert!(uncollected.resulting_accounts.is_empty());
+        assert!(uncollected.program_result.is_ok());
+        assert_eq!(
+            uncollected.compute_units_consumed,
+            collected.compute_units_consumed
+        );
+
+        // Chains still collect, since each step needs the last one's accounts.
+        let chain =
+            mollusk.process_instruction_chain(&[instruction.clone(), instruction], &accounts);
+        assert!(chain.program_result.is_ok());
+        assert_eq!(chain.get_account(&recipient).unwrap().lamports(), 2_000);
+    }
+
+    #[test]
+    fn test_auto_fund_payer() {
+        let mut mollusk = Mollusk::default();
+
+        let payer = Pubkey::new_unique();
+        let new_account = Pubkey::new_unique();
+        let accounts = [
+            (
+                payer,
+                AccountSharedData::new(1_000, 0, &solana_system_program::id()),
+            ),
+            (
+                new_account,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        let space = 64;
+        let rent_exempt_lamports = mollusk.sysvars.rent.minimum_balance(space);
+        let instruction = solana_system_interface::instruction::create_account(
+            &payer,
+            &new_account,
+            rent_exempt_lamports,
+            space as u64,
+            &Pubkey::new_unique(),
+        );
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+
+        mollusk.config.auto_fund_payer = Some(1_000_000_000);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+        assert_eq!(
+            result.get_account(&payer).unwrap().lamports(),
+            1_000_000_000 - rent_exempt_lamports
+        );
+        assert_eq!(
+            result.get_account(&new_account).unwrap().lamports(),
+            rent_exempt_lamports
+        );
+    }
+
+    #[test]
+    fn test_process_and_validate_instruction_with_report() {
+        let mut mollusk = Mollusk::default();
+        mollusk.config.auto_fund_payer = Some(10_000);
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let (result, report) = mollusk.process_and_validate_instruction_with_report(
+            &instruction,
+            &accounts,
+            &[
+                Check::success(),
+                Check::lamports_conserved(),
+                Check::account(&recipient).lamports(1).build(),
+            ],
+        );
+        assert!(result.program_result.is_ok());
+
+        // The funded payer doesn't break conservation, and the failing check
+        // is reported rather than panicking.
+        let passed = report.iter().map(|(_, pass)| *pass).collect::<Vec<_>>();
+        assert_eq!(passed, [true, true, false]);
+    }
+
+    #[test]
+    fn test_auto_fund_payer_checks() {
+        let mut mollusk = Mollusk::default();
+        mollusk.config.auto_fund_payer = Some(10_000);
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        // Checks compare against the funded inputs, so the minted lamports
+        // aren't reported as a leak.
+        let instruction = transfer(&sender, &recipient, 1_000);
+        mollusk.process_and_validate_instruction(
+            &instruction,
+            &accounts,
+            &[
+                Check::success(),
+                Check::lamports_conserved(),
+                Check::account(&sender).lamports(9_000).build(),
+            ],
+        );
+        mollusk.process_and_validate_instruction_chain(
+            &[
+                (&instruction, &[Check::lamports_conserved()]),
+                (&instruction, &[Check::lamports_conserved()]),
+            ],
+            &accounts,
+        );
+
+        // The minted lamports are discarded on failure.
+        let instruction = transfer(&sender, &recipient, 1_000_000);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+        assert_eq!(result.get_account(&sender).unwrap().lamports(), 0);
+    }
+
+    #[test]
+    fn test_max_instruction_data_len() {
+        let mut mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        // A transfer's instruction data is 12 bytes.
+        let instruction = transfer(&sender, &recipient, 1_000);
+
+        mollusk.config.max_instruction_data_len = Some(12);
+        assert!(mollusk
+            .process_instruction(&instruction, &accounts)
+            .program_result
+            .is_ok());
+
+        mollusk.config.max_instruction_data_len = Some(11);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::InvalidInstructionData)
+        );
+        assert_eq!(result.compute_units_consumed, 0);
+        assert_eq!(result.resulting_accounts, accounts.to_vec());
+
+        // Transactions are limited too, stopping at the oversized instruction.
+        let result = mollusk.process_transaction_instructions(
+            &[instruction.clone(), instruction.clone()],
+            &accounts,
+        );
+        assert_eq!(
+            result.raw_result,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::InvalidInstructionData
+            ))
+        );
+
+        // As are raw instructions.
+        let instruction_accounts = [
+            InstructionAccount::new(
+                /* index_in_transaction */ 0, /* is_signer */ true,
+                /* is_writable */ true,
+            ),
+            InstructionAccount::new(
+                /* index_in_transaction */ 1, /* is_signer */ false,
+                /* is_writable */ true,
+            ),
+        ];
+        let (system_program, system_program_account) =
+            crate::program::keyed_account_for_system_program();
+        let mut transaction_accounts = accounts.to_vec();
+        transaction_accounts.push((system_program, system_program_account.into()));
+        let result = mollusk.process_instruction_raw(
+            &instruction,
+            /* program_id_index */ 2,
+            &instruction_accounts,
+            &transaction_accounts,
+        );
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::InvalidInstructionData)
+        );
+
+        // Skipping resulting accounts still applies.
+        mollusk.config.collect_resulting_accounts = false;
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_err());
+        assert!(result.resulting_accounts.is_empty());
+    }
+
+    #[test]
+    fn test_process_instruction_into() {
+        let mollusk = Mollusk::default();
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let mut accounts = HashMap::from([
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ]);
+        let mut out = HashMap::new();
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+
+        for _ in 0..2 {
+            let result = mollusk.process_instruction_into(&transfer, &accounts, &mut out);
+            assert!(result.program_result.is_ok());
+            accounts.extend(out.drain());
+        }
+
+        assert_eq!(accounts.get(&sender).unwrap().lamports(), 8_000);
+        assert_eq!(accounts.get(&recipient).unwrap().lamports(), 2_000);
+    }
+
+    #[test]
+    fn test_process_and_validate_instruction_with_pre_checks() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        mollusk.process_and_validate_instruction_with_pre_checks(
+            &transfer(&sender, &recipient, 1_000),
+            &accounts,
+            &[
+                Check::account(&sender).lamports(10_000).build(),
+                Check::account(&recipient).lamports(0).build(),
+            ],
+            &[
+                Check::success(),
+                Check::account(&sender).lamports(9_000).build(),
+                Check::account(&recipient).lamports(1_000).build(),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_epoch_stake_by_epoch() {
+        let mut mollusk = Mollusk::default();
+        let slots_per_epoch = mollusk.sysvars.epoch_schedule.slots_per_epoch;
+
+        let vote_account = Pubkey::new_unique();
+        mollusk.epoch_stake = EpochStake::from([(vote_account, 1)]);
+        mollusk
+            .epoch_stake_by_epoch
+            .insert(5, EpochStake::from([(vote_account, 500)]));
+        mollusk
+            .epoch_stake_by_epoch
+            .insert(6, EpochStake::from([(vote_account, 600)]));
+
+        let observed_stake = |mollusk: &Mollusk| {
+            let callback = MolluskInvokeContextCallback {
+                epoch_stake: mollusk.current_epoch_stake(),
+                feature_set: &mollusk.feature_set,
+            };
+            (
+                callback.get_epoch_stake(),
+                callback.get_epoch_stake_for_vote_account(&vote_account),
+            )
+        };
+
+        mollusk.warp_to_slot(5 * slots_per_epoch);
+        assert_eq!(observed_stake(&mollusk), (500, 500));
+
+        mollusk.warp_to_slot(6 * slots_per_epoch);
+        assert_eq!(observed_stake(&mollusk), (600, 600));
+
+        // Epochs without an entry fall back to the flat epoch stake.
+        mollusk.warp_to_slot(7 * slots_per_epoch);
+        assert_eq!(observed_stake(&mollusk), (1, 1));
+    }
+
+    #[test]
+    fn test_set_log_writer() {
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut mollusk = Mollusk::default();
+        let buffer = SharedBuffer::default();
+        mollusk.set_log_writer(Box::new(buffer.clone()));
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+
+        let logs = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let system_program = solana_system_program::id();
+        assert!(logs.contains(&format!("Program {system_program} invoke [1]")));
+        assert!(logs.contains(&format!("Program {system_program} success")));
+
+        // Each call only writes its own logs.
+        buffer.0.borrow_mut().clear();
+        mollusk.process_instruction(&instruction, &accounts);
+        let logs = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(logs.matches("invoke [1]").count(), 1);
+    }
+
+    #[test]
+    fn test_try_process_instruction() {
+        let mollusk = Mollusk::default();
+
+        let (sender, recipient, accounts) = transfer_accounts();
+
+        let transfer = |lamports: u64| -> Result<u64, InstructionError> {
+            let instruction =
+                solana_system_interface::instruction::transfer(&sender, &recipient, lamports);
+            let result = mollusk.try_process_instruction(&instruction, &accounts)?;
+            Ok(result.compute_units_consumed)
+        };
+
+        assert!(transfer(1_000).is_ok());
+        assert_eq!(
+            transfer(1_000_000),
+            Err(InstructionError::Custom(
+                solana_system_interface::error::SystemError::ResultWithNegativeLamports as u32
+            ))
+        );
+    }
+
+    #[test]
+    fn test_context_chain_carries_created_accounts() {
+        let payer = Pubkey::new_unique();
+        let created = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let context = Mollusk::default().with_context(HashMap::from([(
+            payer,
+            AccountSharedData::new(10_000_000, 0, &solana_system_program::id()),
+        )]));
+
+        // The second instruction spends lamports from the account created by
+        // the first, which was never present in the store.
+        let instructions = [
+            solana_system_interface::instruction::create_account(
+                &payer,
+                &created,
+                1_000_000,
+                0,
+                &solana_system_program::id(),
+            ),
+            transfer(&created, &recipient, 400_000),
+        ];
+
+        let result = context.process_instruction_chain(&instructions);
+        assert!(result.program_result.is_ok());
+
+        let store = context.account_store.borrow();
+        assert_eq!(store.get(&created).unwrap().lamports(), 600_000);
+        assert_eq!(store.get(&recipient).unwrap().lamports(), 400_000);
+    }
+
+    #[test]
+    fn test_context_fork() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let context = Mollusk::default().with_context(HashMap::from([(
+            sender,
+            AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+        )]));
+
+        let transfer = solana_system_interface::instruction::transfer(&sender, &recipient, 1_000);
+        assert!(context
+            .process_instruction(&transfer)
+            .program_result
+            .is_ok());
+        assert!(context
+            .process_instruction(&transfer)
+            .program_result
+            .is_ok());
+
+        let fork = context.fork().unwrap();
+        let fork_store = fork.account_store.borrow().clone();
+        assert_eq!(&fork_store, &*context.account_store.borrow());
+
+        // Mutating the fork leaves the parent untouched.
+        assert!(fork.process_instruction(&transfer).program_result.is_ok());
+        fork.account_store.borrow_mut().insert(
+            recipient,
+            AccountSharedData::new(0, 0, &solana_system_program::id()),
+        );
+
+        let parent_store = context.account_store.borrow();
+        assert_eq!(parent_store.get(&sender).unwrap().lamports(), 8_000);
+        assert_eq!(parent_store.get(&recipient).unwrap().lamports(), 2_000);
+
+        let fork_store = fork.account_store.borrow();
+        assert_eq!(fork_store.get(&sender).unwrap().lamports(), 7_000);
+        assert_eq!(fork_store.get(&recipient).unwrap().lamports(), 0);
+    }
+
+    #[test]
+    fn test_context_fork_unsupported_store() {
+        struct OpaqueStore(HashMap<Pubkey, AccountSharedData>);
+
+        impl AccountStore for OpaqueStore {
+            fn get_account(&self, pubkey: &Pubkey) -> Option<AccountSharedData> {
+                self.0.get_account(pubkey)
+            }
+
+            fn store_account(&mut self, pubkey: Pubkey, account: AccountSharedData) {
+                self.0.store_account(pubkey, account);
+            }
+        }
+
+        let context = Mollusk::default().with_context(OpaqueStore(HashMap::new()));
+        assert!(context.fork().is_none());
+    }
+
+    #[test]
+    fn test_context_persist_partial_chain() {
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let mut context = Mollusk::default().with_context(HashMap::from([(
+            sender,
+            AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+        )]));
+        context.persist_partial_chain = true;
+
+        // The third transfer overdraws the sender.
+        let instructions = [
+            transfer(&sender, &recipient, 1_000),
+            transfer(&sender, &recipient, 2_000),
+            transfer(&sender, &recipient, 1_000_000),
+        ];
+
+        let result = context.process_instruction_chain(&instructions);
+        assert!(result.program_result.is_err());
+
+        {
+            let store = context.account_store.borrow();
+            assert_eq!(store.get(&sender).unwrap().lamports(), 7_000);
+            assert_eq!(store.get(&recipient).unwrap().lamports(), 3_000);
+        }
+
+        // The same applies to validated chains.
+        let result = context.process_and_validate_instruction_chain(&[
+            (&instructions[0], &[Check::success()]),
+            (&instructions[2], &[]),
+        ]);
+        assert!(result.program_result.is_err());
+
+        let store = context.account_store.borrow();
+        assert_eq!(store.get(&sender).unwrap().lamports(), 6_000);
+        assert_eq!(store.get(&recipient).unwrap().lamports(), 4_000);
+    }
+
+    #[test]
+    fn test_set_max_trace_length() {
+        let mut mollusk = Mollusk::default();
+        mollusk.set_max_trace_length(2);
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instructions = vec![transfer(&sender, &recipient, 1_000); 4];
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+        ];
+
+        let result = mollusk.process_transaction_instructions(&instructions, &accounts);
+        assert!(matches!(
+            result.raw_result,
+            Err(TransactionError::InstructionError(
+                _,
+                InstructionError::MaxInstructionTraceLengthExceeded
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_remove_builtin() {
+        let mut mollusk = Mollusk::default();
+        mollusk.remove_builtin(&solana_system_program::id());
+
+        let sender = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instruction = transfer(&sender, &recipient, 1_000);
+        let accounts = [
+            (
+                sender,
+                AccountSharedData::new(10_000, 0, &solana_system_program::id()),
+            ),
+            (
+                recipient,
+                AccountSharedData::new(0, 0, &solana_system_program::id()),
+            ),
+            {
+                let (key, account) = crate::program::keyed_account_for_system_program();
+                (key, account.into())
+            },
+        ];
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(
+            result.raw_result,
+            Err(InstructionError::UnsupportedProgramId)
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Account modification tracking.
+//!
+//! A `ModificationTrackingCallback` records which accounts each top-level
+//! instruction modified, along with their lamports and data sizes before and
+//! after the invocation. It's more focused than a full trace, for tests that
+//! only care about what was written.
+
+use {
+    crate::{InvocationInspectCallback, Mollusk},
+    solana_account::ReadableAccount,
+    solana_program_runtime::invoke_context::InvokeContext,
+    solana_pubkey::Pubkey,
+    solana_transaction_context::{InstructionAccount, TransactionContext},
+    std::{cell::RefCell, rc::Rc},
+};
+
+/// A modification made to an account by an instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountModification {
+    pub pubkey: Pubkey,
+    pub lamports_before: u64,
+    pub lamports_after: u64,
+    pub size_before: usize,
+    pub size_after: usize,
+}
+
+struct AccountSnapshot {
+    pubkey: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+/// An invocation inspect callback recording the accounts modified by each
+/// instruction.
+///
+/// The callback is cheap to clone, and clones share the same log, so keep a
+/// clone around to read the modifications after boxing one into
+/// `Mollusk::invocation_inspect_callback`. Clones of the `Mollusk` instance
+/// share the log as well.
+///
+/// An account counts as modified if its lamports or data changed.
+#[derive(Clone, Default)]
+pub struct ModificationTrackingCallback {
+    snapshot: Rc<RefCell<Vec<AccountSnapshot>>>,
+    modifications: Rc<RefCell<Vec<AccountModification>>>,
+}
+
+impl ModificationTrackingCallback {
+    /// Get every modification recorded so far, in order.
+    pub fn modifications(&self) -> Vec<AccountModification> {
+        self.modifications.borrow().clone()
+    }
+
+    /// Clear the recorded modifications.
+    pub fn clear(&self) {
+        self.modifications.borrow_mut().clear();
+    }
+
+    fn snapshot(transaction_context: &TransactionContext) -> Vec<AccountSnapshot> {
+        (0..transaction_context.get_number_of_accounts())
+            .map(|index| {
+                let pubkey = transaction_context
+                    .get_key_of_account_at_index(index)
+                    .unwrap();
+                let account_ref = transaction_context.accounts().try_borrow(index).unwrap();
+                AccountSnapshot {
+                    pubkey: *pubkey,
+                    lamports: account_ref.lamports(),
+                    data: account_ref.data().to_vec(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl InvocationInspectCallback for ModificationTrackingCallback {
+    fn before_invocation(
+        &self,
+        _: &Mollusk,
+        _: &Pubkey,
+        _: &[u8],
+        _: &[InstructionAccount],
+        invoke_context: &InvokeContext,
+    ) {
+        *self.snapshot.borrow_mut() = Self::snapshot(invoke_context.transaction_context);
+    }
+
+    fn after_invocation(
+        &self,
+        _: &Mollusk,
+        invoke_context: &InvokeContext,
+        _register_tracing_enabled: bool,
+    ) {
+        let before = std::mem::take(&mut *self.snapshot.borrow_mut());
+        let after = Self::snapshot(invoke_context.transaction_context);
+        self.modifications.borrow_mut().extend(
+            before
+                .iter()
+                .zip(after.iter())
+                .filter(|(before, after)| {
+                    before.lamports != after.lamports || before.data != after.data
+                })
+                .map(|(before, after)| AccountModification {
+                    pubkey: before.pubkey,
+                    lamports_before: before.lamports,
+                    lamports_after: after.lamports,
+                    size_before: before.data.len(),
+                    size_after: after.data.len(),
+                }),
+        );
+    }
+
+    fn clone_callback(&self) -> Option<Box<dyn InvocationInspectCallback>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_account::AccountSharedData};
+
+    #[test]
+    fn test_modification_tracking() {
+        let mut mollusk = Mollusk::default();
+        let tracker = ModificationTrackingCallback::default();
+        mollusk.invocation_inspect_callback = Box::new(tracker.clone());
+
+        let account = Pubkey::new_unique();
+        let accounts = [(
+            account,
+            AccountSharedData::new(10_000_000, 0, &solana_system_program::id()),
+        )];
+
+        let instruction = solana_system_interface::instruction::allocate(&account, 64);
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+
+        assert_eq!(
+            tracker.modifications(),
+            vec![AccountModification {
+                pubkey: account,
+                lamports_before: 10_000_000,
+                lamports_after: 10_000_000,
+                size_before: 0,
+                size_after: 64,
+            }]
+        );
+
+        tracker.clear();
+        assert!(tracker.modifications().is_empty());
+
+        // Clones share the callback.
+        let result = mollusk.clone().process_instruction(&instruction, &accounts);
+        assert!(result.program_result.is_ok());
+        assert_eq!(tracker.modifications().len(), 1);
+    }
+}
@@ -9,7 +9,10 @@ use {
     solana_loader_v4_interface::state::{LoaderV4State, LoaderV4Status},
     solana_program_runtime::{
         invoke_context::{BuiltinFunctionWithContext, InvokeContext},
-        loaded_programs::{LoadProgramMetrics, ProgramCacheEntry, ProgramCacheForTxBatch},
+        loaded_programs::{
+            LoadProgramMetrics, ProgramCacheEntry, ProgramCacheEntryOwner, ProgramCacheEntryType,
+            ProgramCacheForTxBatch,
+        },
         solana_sbpf::program::BuiltinProgram,
     },
     solana_pubkey::Pubkey,
@@ -56,6 +59,7 @@ pub mod precompile_keys {
     }
 }
 
+#[derive(Clone)]
 pub struct CacheEntry {
     pub loader_key: Pubkey,
     pub elf_bytes: Option<Vec<u8>>,
@@ -84,7 +88,21 @@ impl ProgramCache {
         compute_budget: &ComputeBudget,
         enable_register_tracing: bool,
     ) -> Self {
-        let me = Self {
+        let me = Self::empty(feature_set, compute_budget, enable_register_tracing);
+        BUILTINS.iter().for_each(|builtin| {
+            let program_id = builtin.program_id;
+            let entry = builtin.program_cache_entry();
+            me.replenish(program_id, entry, None);
+        });
+        me
+    }
+
+    fn empty(
+        feature_set: &FeatureSet,
+        compute_budget: &ComputeBudget,
+        enable_register_tracing: bool,
+    ) -> Self {
+        Self {
             cache: Rc::new(RefCell::new(ProgramCacheForTxBatch::default())),
             entries_cache: Rc::new(RefCell::new(HashMap::new())),
             program_runtime_environment: create_program_runtime_environment_v1(
@@ -94,13 +112,54 @@ impl ProgramCache {
                 /* debugging_features */ enable_register_tracing,
             )
             .unwrap(),
-        };
-        BUILTINS.iter().for_each(|builtin| {
-            let program_id = builtin.program_id;
-            let entry = builtin.program_cache_entry();
-            me.replenish(program_id, entry, None);
-        });
-        me
+        }
+    }
+
+    /// Create a copy of the cache with every program reloaded under the
+    /// provided feature set and compute budget.
+    ///
+    /// Only programs with tracked ELF bytes (ie. those added with
+    /// `add_program`) can be reloaded. If a program's ELF fails to load under
+    /// the new environment, it's cached as failing verification, like the
+    /// runtime would. Every other entry, such as builtins and entries
+    /// inserted with `insert_entry`, is carried over as-is.
+    pub(crate) fn reload(
+        &self,
+        feature_set: &FeatureSet,
+        compute_budget: &ComputeBudget,
+        enable_register_tracing: bool,
+    ) -> Self {
+        let reloaded = Self::empty(feature_set, compute_budget, enable_register_tracing);
+        for (program_id, cache_entry) in self.entries_cache.borrow().iter() {
+            let entry = match &cache_entry.elf_bytes {
+                Some(elf) => {
+                    let environment = Arc::new(reloaded.duplicate_runtime_environment());
+                    ProgramCacheEntry::new(
+                        &cache_entry.loader_key,
+                        environment.clone(),
+                        0,
+                        0,
+                        elf,
+                        elf.len(),
+                        &mut LoadProgramMetrics::default(),
+                    )
+                    .map(Arc::new)
+                    .unwrap_or_else(|_| {
+                        Arc::new(ProgramCacheEntry::new_tombstone(
+                            0,
+                            ProgramCacheEntryOwner::try_from(&cache_entry.loader_key).unwrap(),
+                            ProgramCacheEntryType::FailedVerification(environment),
+                        ))
+                    })
+                }
+                None => match self.load_program(program_id) {
+                    Some(entry) => entry,
+                    None => continue,
+                },
+            };
+            reloaded.replenish(*program_id, entry, cache_entry.elf_bytes.as_deref());
+        }
+        reloaded
     }
 
     pub(crate) fn cache(&self) -> RefMut<'_, ProgramCacheForTxBatch> {
@@ -130,25 +189,74 @@ impl ProgramCache {
         self.replenish(program_id, entry, None);
     }
 
-    /// Add a program to the cache.
-    pub fn add_program(&mut self, program_id: &Pubkey, loader_key: &Pubkey, elf: &[u8]) {
-        // This might look rough, but it's actually functionally the same as
-        // calling `create_program_runtime_environment_v1` on every addition.
-        let environment = {
-            let config = self.program_runtime_environment.get_config().clone();
-            let mut loader = BuiltinProgram::new_loader(config);
-
-            for (_key, (name, value)) in self
-                .program_runtime_environment
-                .get_function_registry()
-                .iter()
-            {
-                let name = std::str::from_utf8(name).unwrap();
-                loader.register_function(name, value).unwrap();
+    /// Insert a prebuilt entry into the cache, replacing any existing entry
+    /// for the program.
+    ///
+    /// This is intended for advanced setups, such as testing loader internals
+    /// with tombstone or delay-visibility entries.
+    ///
+    /// Warning: The entry is inserted as-is. It is not verified against the
+    /// cache's program runtime environment, and any instruction targeting an
+    /// entry without an executable (ie. a tombstone) will fail. No ELF bytes
+    /// are tracked for the program either, so `get_program_elf_bytes` will
+    /// return `None` for it.
+    pub fn insert_entry(&mut self, program_id: &Pubkey, entry: Arc<ProgramCacheEntry>) {
+        self.replenish(*program_id, entry, None);
+    }
+
+    /// Reinsert an entry previously loaded from the cache, along with the ELF
+    /// bytes tracked for it, if any.
+    pub(crate) fn restore_entry(
+        &mut self,
+        program_id: &Pubkey,
+        entry: Arc<ProgramCacheEntry>,
+        elf_bytes: Option<&[u8]>,
+    ) {
+        self.replenish(*program_id, entry, elf_bytes);
+    }
+
+    /// Remove a program from the cache, returning its entry if it was cached.
+    ///
+    /// Instructions targeting the program will then fail with
+    /// `InstructionError::UnsupportedProgramId`.
+    pub fn remove_program(&mut self, program_id: &Pubkey) -> Option<Arc<ProgramCacheEntry>> {
+        let removed = self.load_program(program_id);
+        self.entries_cache.borrow_mut().remove(program_id);
+
+        // `ProgramCacheForTxBatch` doesn't support eviction, so rebuild it
+        // from the remaining tracked entries.
+        let mut cache = ProgramCacheForTxBatch::default();
+        for key in self.entries_cache.borrow().keys() {
+            if let Some(entry) = self.load_program(key) {
+                cache.replenish(*key, entry);
             }
+        }
+        *self.cache.borrow_mut() = cache;
 
-            Arc::new(loader)
-        };
+        removed
+    }
+
+    // This might look rough, but it's actually functionally the same as
+    // calling `create_program_runtime_environment_v1` again.
+    fn duplicate_runtime_environment(&self) -> BuiltinProgram<InvokeContext<'static, 'static>> {
+        let config = self.program_runtime_environment.get_config().clone();
+        let mut loader = BuiltinProgram::new_loader(config);
+
+        for (_key, (name, value)) in self
+            .program_runtime_environment
+            .get_function_registry()
+            .iter()
+        {
+            let name = std::str::from_utf8(name).unwrap();
+            loader.register_function(name, value).unwrap();
+        }
+
+        loader
+    }
+
+    /// Add a program to the cache.
+    pub fn add_program(&mut self, program_id: &Pubkey, loader_key: &Pubkey, elf: &[u8]) {
+        let environment = Arc::new(self.duplicate_runtime_environment());
         self.replenish(
             *program_id,
             Arc::new(
@@ -212,6 +320,13 @@ impl ProgramCache {
             })
     }
 
+    /// Get the IDs of every program in the cache, sorted.
+    pub fn cached_program_ids(&self) -> Vec<Pubkey> {
+        let mut program_ids: Vec<Pubkey> = self.entries_cache.borrow().keys().copied().collect();
+        program_ids.sort();
+        program_ids
+    }
+
     pub fn get_program_elf_bytes(&self, program_id: &Pubkey) -> Option<Vec<u8>> {
         match self.entries_cache.borrow().get(program_id) {
             None => None,
@@ -220,6 +335,19 @@ impl ProgramCache {
     }
 }
 
+impl Clone for ProgramCache {
+    /// Creates an independent copy of the cache. Entries themselves are
+    /// reference-counted, so this is cheap, but programs added to one cache
+    /// afterwards won't be visible to the other.
+    fn clone(&self) -> Self {
+        Self {
+            cache: Rc::new(RefCell::new(self.cache.borrow().clone())),
+            entries_cache: Rc::new(RefCell::new(self.entries_cache.borrow().clone())),
+            program_runtime_environment: self.duplicate_runtime_environment(),
+        }
+    }
+}
+
 pub struct Builtin {
     program_id: Pubkey,
     name: &'static str,
@@ -252,19 +380,19 @@ static BUILTINS: &[Builtin] = &[
         name: "solana_bpf_loader_upgradeable_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
     },
-    #[cfg(feature = "all-builtins")]
+    #[cfg(feature = "builtin-loader-v1")]
     Builtin {
         program_id: loader_keys::LOADER_V1,
         name: "solana_bpf_loader_deprecated_program",
         entrypoint: solana_bpf_loader_program::Entrypoint::vm,
     },
-    #[cfg(feature = "all-builtins")]
+    #[cfg(feature = "builtin-loader-v4")]
     Builtin {
         program_id: loader_keys::LOADER_V4,
         name: "solana_loader_v4_program",
         entrypoint: solana_loader_v4_program::Entrypoint::vm,
     },
-    #[cfg(feature = "all-builtins")]
+    #[cfg(feature = "builtin-zk-elgamal-proof")]
     Builtin {
         program_id: solana_sdk_ids::zk_elgamal_proof_program::id(),
         name: "zk_elgamal_proof_program",
@@ -389,6 +517,18 @@ pub fn create_program_account_pair_loader_v3(
     )
 }
 
+/// Decode the `UpgradeableLoaderState` of a BPF Loader v3 (Upgradeable)
+/// account, such as a program or program data account.
+///
+/// Returns `None` if the account isn't owned by the loader, or its data
+/// doesn't hold a valid state.
+pub fn decode_upgradeable_state(account: &Account) -> Option<UpgradeableLoaderState> {
+    if account.owner != loader_keys::LOADER_V3 {
+        return None;
+    }
+    bincode::deserialize(&account.data).ok()
+}
+
 /// Create a BPF Loader 4 program account.
 pub fn create_program_account_loader_v4(elf: &[u8]) -> Account {
     let data = unsafe {
@@ -414,3 +554,226 @@ pub fn create_program_account_loader_v4(elf: &[u8]) -> Account {
         ..Default::default()
     }
 }
+
+/// Create a minimal SBPFv0 ELF for a program that does nothing but return
+/// success, for tests that need a program without depending on a build
+/// artifact.
+///
+/// The ELF holds a single load segment with a `.text` section of two
+/// instructions (`mov64 r0, 0` and `exit`), followed by the section name
+/// string table and the section header table.
+pub fn create_noop_program_elf() -> Vec<u8> {
+    const EHDR_SIZE: u16 = 64;
+    const PHDR_SIZE: u16 = 56;
+    const SHDR_SIZE: u16 = 64;
+
+    const TEXT: [u8; 16] = [
+        0xb7, 0, 0, 0, 0, 0, 0, 0, // mov64 r0, 0
+        0x95, 0, 0, 0, 0, 0, 0, 0, // exit
+    ];
+    const SHSTRTAB: &[u8] = b"\0.text\0.shstrtab\0";
+
+    const PHDR_OFFSET: u64 = EHDR_SIZE as u64;
+    const TEXT_OFFSET: u64 = PHDR_OFFSET + PHDR_SIZE as u64;
+    const SHSTRTAB_OFFSET: u64 = TEXT_OFFSET + TEXT.len() as u64;
+    // The section header table must be 8-byte aligned.
+    const SHDR_OFFSET: u64 = (SHSTRTAB_OFFSET + SHSTRTAB.len() as u64).next_multiple_of(8);
+
+    #[allow(clippy::too_many_arguments)]
+    fn section_header(
+        elf: &mut Vec<u8>,
+        name: u32,
+        sh_type: u32,
+        flags: u64,
+        addr: u64,
+        offset: u64,
+        size: u64,
+        align: u64,
+    ) {
+        elf.extend_from_slice(&name.to_le_bytes());
+        elf.extend_from_slice(&sh_type.to_le_bytes());
+        elf.extend_from_slice(&flags.to_le_bytes());
+        elf.extend_from_slice(&addr.to_le_bytes());
+        elf.extend_from_slice(&offset.to_le_bytes());
+        elf.extend_from_slice(&size.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_link
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+        elf.extend_from_slice(&align.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes()); // sh_entsize
+    }
+
+    let mut elf = Vec::with_capacity(SHDR_OFFSET as usize + 3 * SHDR_SIZE as usize);
+
+    // File header: 64-bit, little-endian, System V ABI, shared object, BPF.
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    elf.extend_from_slice(&3u16.to_le_bytes()); // e_type: ET_DYN
+    elf.extend_from_slice(&247u16.to_le_bytes()); // e_machine: EM_BPF
+    elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    elf.extend_from_slice(&TEXT_OFFSET.to_le_bytes()); // e_entry
+    elf.extend_from_slice(&PHDR_OFFSET.to_le_bytes()); // e_phoff
+    elf.extend_from_slice(&SHDR_OFFSET.to_le_bytes()); // e_shoff
+    elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags: SBPFv0
+    elf.extend_from_slice(&EHDR_SIZE.to_le_bytes());
+    elf.extend_from_slice(&PHDR_SIZE.to_le_bytes());
+    elf.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+    elf.extend_from_slice(&SHDR_SIZE.to_le_bytes());
+    elf.extend_from_slice(&3u16.to_le_bytes()); // e_shnum
+    elf.extend_from_slice(&2u16.to_le_bytes()); // e_shstrndx
+
+    // Program header: a single readable, executable load segment.
+    elf.extend_from_slice(&1u32.to_le_bytes()); // p_type: PT_LOAD
+    elf.extend_from_slice(&5u32.to_le_bytes()); // p_flags: PF_R | PF_X
+    elf.extend_from_slice(&TEXT_OFFSET.to_le_bytes()); // p_offset
+    elf.extend_from_slice(&TEXT_OFFSET.to_le_bytes()); // p_vaddr
+    elf.extend_from_slice(&TEXT_OFFSET.to_le_bytes()); // p_paddr
+    elf.extend_from_slice(&(TEXT.len() as u64).to_le_bytes()); // p_filesz
+    elf.extend_from_slice(&(TEXT.len() as u64).to_le_bytes()); // p_memsz
+    elf.extend_from_slice(&0x1000u64.to_le_bytes()); // p_align
+
+    elf.extend_from_slice(&TEXT);
+    elf.extend_from_slice(SHSTRTAB);
+    elf.resize(SHDR_OFFSET as usize, 0);
+
+    // Section headers: null, `.text`, and `.shstrtab`.
+    section_header(&mut elf, 0, 0, 0, 0, 0, 0, 0);
+    section_header(
+        &mut elf,
+        /* name */ 1,
+        /* sh_type: SHT_PROGBITS */ 1,
+        /* flags: SHF_ALLOC | SHF_EXECINSTR */ 6,
+        /* addr */ TEXT_OFFSET,
+        /* offset */ TEXT_OFFSET,
+        /* size */ TEXT.len() as u64,
+        /* align */ 8,
+    );
+    section_header(
+        &mut elf,
+        /* name */ 7,
+        /* sh_type: SHT_STRTAB */ 3,
+        /* flags */ 0,
+        /* addr */ 0,
+        /* offset */ SHSTRTAB_OFFSET,
+        /* size */ SHSTRTAB.len() as u64,
+        /* align */ 1,
+    );
+
+    elf
+}
+
+/// Set the `rent_epoch` of an account, such as one created by the helpers in
+/// this module, which leave it at `0`.
+///
+/// Rent-exempt accounts on mainnet have a `rent_epoch` of `u64::MAX`, so use
+/// this to reproduce them faithfully for programs that branch on it.
+pub fn with_rent_epoch(mut account: Account, rent_epoch: u64) -> Account {
+    account.rent_epoch = rent_epoch;
+    account
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_entry() {
+        let mut program_cache = ProgramCache::new(
+            &FeatureSet::all_enabled(),
+            &ComputeBudget::new_with_defaults(true, true),
+            false,
+        );
+
+        let program_id = Pubkey::new_unique();
+        assert!(program_cache.load_program(&program_id).is_none());
+
+        let entry = Arc::new(ProgramCacheEntry::new_builtin(
+            0,
+            0,
+            solana_system_program::system_processor::Entrypoint::vm,
+        ));
+        program_cache.insert_entry(&program_id, Arc::clone(&entry));
+
+        let loaded = program_cache.load_program(&program_id).unwrap();
+        assert!(Arc::ptr_eq(&loaded, &entry));
+        assert_eq!(loaded.account_owner(), loader_keys::NATIVE_LOADER);
+        assert!(program_cache.get_program_elf_bytes(&program_id).is_none());
+    }
+
+    #[test]
+    fn test_remove_program() {
+        let mut program_cache = ProgramCache::new(
+            &FeatureSet::all_enabled(),
+            &ComputeBudget::new_with_defaults(true, true),
+            false,
+        );
+
+        let system_program = solana_system_program::id();
+        assert!(program_cache.remove_program(&system_program).is_some());
+        assert!(program_cache.load_program(&system_program).is_none());
+        assert!(program_cache
+            .maybe_create_program_account(&system_program)
+            .is_none());
+
+        // Other builtins are untouched.
+        assert!(program_cache
+            .load_program(&loader_keys::LOADER_V3)
+            .is_some());
+
+        // Removing it again is a no-op.
+        assert!(program_cache.remove_program(&system_program).is_none());
+    }
+
+    #[test]
+    fn test_decode_upgradeable_state() {
+        let program_id = Pubkey::new_unique();
+        let elf = [1, 2, 3, 4];
+        let (program_account, mut program_data_account) =
+            create_program_account_pair_loader_v3(&program_id, &elf);
+
+        assert_eq!(
+            decode_upgradeable_state(&program_account),
+            Some(UpgradeableLoaderState::Program {
+                programdata_address: Pubkey::find_program_address(
+                    &[program_id.as_ref()],
+                    &loader_keys::LOADER_V3
+                )
+                .0,
+            })
+        );
+
+        let authority = Pubkey::new_unique();
+        bincode::serialize_into(
+            &mut program_data_account.data[..],
+            &UpgradeableLoaderState::ProgramData {
+                slot: 42,
+                upgrade_authority_address: Some(authority),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            decode_upgradeable_state(&program_data_account),
+            Some(UpgradeableLoaderState::ProgramData {
+                slot: 42,
+                upgrade_authority_address: Some(authority),
+            })
+        );
+
+        // Accounts owned by other loaders are never decoded.
+        assert_eq!(
+            decode_upgradeable_state(&create_program_account_loader_v2(&elf)),
+            None
+        );
+    }
+
+    #[cfg(feature = "builtin-loader-v4")]
+    #[test]
+    fn test_builtin_loader_v4() {
+        let program_cache = ProgramCache::new(
+            &FeatureSet::all_enabled(),
+            &ComputeBudget::new_with_defaults(true, true),
+            false,
+        );
+
+        let entry = program_cache.load_program(&loader_keys::LOADER_V4).unwrap();
+        assert_eq!(entry.account_owner(), loader_keys::NATIVE_LOADER);
+    }
+}
@@ -107,6 +107,12 @@ impl InvocationInspectCallback for DefaultRegisterTracingCallback {
             );
         }
     }
+
+    fn clone_callback(&self) -> Option<Box<dyn InvocationInspectCallback>> {
+        Some(Box::new(Self {
+            sbf_trace_dir: self.sbf_trace_dir.clone(),
+        }))
+    }
 }
 
 pub(crate) fn as_bytes<T>(slice: &[T]) -> &[u8] {
@@ -1,6 +1,7 @@
 //! Module for working with Solana sysvars.
 
 use {
+    mollusk_svm_error::error::{MolluskError, MolluskPanic},
     solana_account::{Account, AccountSharedData, ReadableAccount},
     solana_clock::{Clock, Slot},
     solana_epoch_rewards::EpochRewards,
@@ -18,6 +19,7 @@ use {
 // Agave's sysvar cache is difficult to work with, so Mollusk offers a wrapper
 // around it for modifying its contents.
 /// Mollusk sysvars.
+#[derive(Clone)]
 pub struct Sysvars {
     pub clock: Clock,
     pub epoch_rewards: EpochRewards,
@@ -92,6 +94,36 @@ impl Sysvars {
         }
     }
 
+    fn deserialize_sysvar<T: SysvarSerialize>(pubkey: &Pubkey, account: &Account) -> T {
+        bincode::deserialize::<T>(&account.data)
+            .or_panic_with(MolluskError::InvalidSysvarAccount(pubkey))
+    }
+
+    /// Overwrite sysvars with the contents of the provided sysvar accounts,
+    /// such as those captured from a cluster.
+    ///
+    /// Accounts whose keys aren't sysvars are ignored. Panics if a sysvar
+    /// account's data can't be deserialized.
+    pub fn set_from_accounts(&mut self, accounts: &[(Pubkey, Account)]) {
+        for (pubkey, account) in accounts {
+            if pubkey.eq(&Clock::id()) {
+                self.clock = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&EpochRewards::id()) {
+                self.epoch_rewards = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&EpochSchedule::id()) {
+                self.epoch_schedule = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&LastRestartSlot::id()) {
+                self.last_restart_slot = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&Rent::id()) {
+                self.rent = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&SlotHashes::id()) {
+                self.slot_hashes = Self::deserialize_sysvar(pubkey, account);
+            } else if pubkey.eq(&StakeHistory::id()) {
+                self.stake_history = Self::deserialize_sysvar(pubkey, account);
+            }
+        }
+    }
+
     /// Get the key and account for the clock sysvar.
     pub fn keyed_account_for_clock_sysvar(&self) -> (Pubkey, Account) {
         self.sysvar_account(&self.clock)
@@ -127,6 +159,20 @@ impl Sysvars {
         self.sysvar_account(&self.stake_history)
     }
 
+    /// Get the keys and accounts for the requested sysvars, in the order
+    /// requested, reflecting their current state.
+    ///
+    /// Keys that don't belong to a sysvar are skipped.
+    pub fn keyed_accounts_for(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, Account)> {
+        pubkeys
+            .iter()
+            .filter_map(|pubkey| {
+                self.maybe_create_sysvar_account(pubkey)
+                    .map(|account| (*pubkey, account))
+            })
+            .collect()
+    }
+
     pub(crate) fn get_all_keyed_sysvar_accounts(&self) -> Vec<(Pubkey, Account)> {
         vec![
             self.keyed_account_for_clock_sysvar(),
@@ -179,6 +225,32 @@ impl Sysvars {
         }
     }
 
+    /// Set the entries of the `SlotHashes` sysvar, most recent slot first.
+    pub fn set_slot_hashes(&mut self, entries: Vec<(Slot, Hash)>) {
+        self.slot_hashes = SlotHashes::new(&entries);
+    }
+
+    /// Set the `StakeHistory` sysvar.
+    pub fn set_stake_history(&mut self, history: StakeHistory) {
+        self.stake_history = history;
+    }
+
+    /// Set the `EpochRewards` sysvar.
+    ///
+    /// By default, the sysvar is in its inactive state, as it is outside of
+    /// the partitioned rewards distribution phase. Set `active` to simulate
+    /// an in-progress distribution.
+    pub fn set_epoch_rewards(&mut self, rewards: EpochRewards) {
+        self.epoch_rewards = rewards;
+    }
+
+    /// Set the slot of the `LastRestartSlot` sysvar, which defaults to `0`.
+    pub fn set_last_restart_slot(&mut self, slot: Slot) {
+        self.last_restart_slot = LastRestartSlot {
+            last_restart_slot: slot,
+        };
+    }
+
     pub(crate) fn setup_sysvar_cache(
         &self,
         accounts: &[(Pubkey, AccountSharedData)],
@@ -284,6 +356,168 @@ mod tests {
         warp_and_check(800_000);
     }
 
+    #[test]
+    fn test_set_slot_hashes() {
+        let mut sysvars = Sysvars::default();
+
+        let entries = vec![(42, Hash::new_from_array([1; 32])), (41, Hash::default())];
+        sysvars.set_slot_hashes(entries.clone());
+
+        let sysvar_cache = sysvars.setup_sysvar_cache(&[]);
+        assert_eq!(
+            sysvar_cache.get_slot_hashes().unwrap().as_slice(),
+            entries.as_slice()
+        );
+
+        let (key, account) = sysvars
+            .get_all_keyed_sysvar_accounts()
+            .into_iter()
+            .find(|(key, _)| key == &SlotHashes::id())
+            .unwrap();
+        assert_eq!(key, SlotHashes::id());
+        assert_eq!(
+            bincode::deserialize::<SlotHashes>(&account.data)
+                .unwrap()
+                .as_slice(),
+            entries.as_slice()
+        );
+    }
+
+    #[test]
+    fn test_set_stake_history() {
+        let mut sysvars = Sysvars::default();
+
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(
+            7,
+            StakeHistoryEntry {
+                effective: 100,
+                activating: 20,
+                deactivating: 3,
+            },
+        );
+        sysvars.set_stake_history(stake_history.clone());
+
+        let sysvar_cache = sysvars.setup_sysvar_cache(&[]);
+        assert_eq!(
+            sysvar_cache.get_stake_history().unwrap().deref(),
+            &stake_history
+        );
+
+        let (_, account) = sysvars
+            .get_all_keyed_sysvar_accounts()
+            .into_iter()
+            .find(|(key, _)| key == &StakeHistory::id())
+            .unwrap();
+        assert_eq!(
+            bincode::deserialize::<StakeHistory>(&account.data).unwrap(),
+            stake_history
+        );
+    }
+
+    #[test]
+    fn test_set_epoch_rewards() {
+        let mut sysvars = Sysvars::default();
+        assert!(!sysvars.epoch_rewards.active);
+
+        let epoch_rewards = EpochRewards {
+            distribution_starting_block_height: 42,
+            num_partitions: 4,
+            parent_blockhash: Hash::new_from_array([7; 32]),
+            total_points: 1_000,
+            total_rewards: 500,
+            distributed_rewards: 100,
+            active: true,
+        };
+        sysvars.set_epoch_rewards(epoch_rewards.clone());
+
+        let sysvar_cache = sysvars.setup_sysvar_cache(&[]);
+        assert_eq!(
+            sysvar_cache.get_epoch_rewards().unwrap().deref(),
+            &epoch_rewards
+        );
+
+        let (_, account) = sysvars
+            .get_all_keyed_sysvar_accounts()
+            .into_iter()
+            .find(|(key, _)| key == &EpochRewards::id())
+            .unwrap();
+        assert_eq!(
+            bincode::deserialize::<EpochRewards>(&account.data).unwrap(),
+            epoch_rewards
+        );
+    }
+
+    #[test]
+    fn test_set_last_restart_slot() {
+        let mut sysvars = Sysvars::default();
+        assert_eq!(sysvars.last_restart_slot.last_restart_slot, 0);
+
+        sysvars.set_last_restart_slot(42);
+
+        let sysvar_cache = sysvars.setup_sysvar_cache(&[]);
+        assert_eq!(
+            sysvar_cache
+                .get_last_restart_slot()
+                .unwrap()
+                .last_restart_slot,
+            42
+        );
+
+        let (_, account) = sysvars.keyed_account_for_last_restart_slot_sysvar();
+        assert_eq!(
+            bincode::deserialize::<LastRestartSlot>(&account.data)
+                .unwrap()
+                .last_restart_slot,
+            42
+        );
+    }
+
+    #[test]
+    fn test_keyed_accounts_for() {
+        let mut sysvars = Sysvars::default();
+        sysvars.warp_to_slot(42);
+        sysvars.rent.lamports_per_byte_year = 7;
+
+        let accounts = sysvars.keyed_accounts_for(&[Clock::id(), Pubkey::new_unique(), Rent::id()]);
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].0, Clock::id());
+        assert_eq!(
+            bincode::deserialize::<Clock>(&accounts[0].1.data)
+                .unwrap()
+                .slot,
+            42
+        );
+        assert_eq!(accounts[1].0, Rent::id());
+        assert_eq!(
+            bincode::deserialize::<Rent>(&accounts[1].1.data).unwrap(),
+            sysvars.rent
+        );
+    }
+
+    #[test]
+    fn test_set_from_accounts() {
+        let mut captured = Sysvars::default();
+        captured.warp_to_slot(1_000);
+        captured.rent.lamports_per_byte_year = 7;
+
+        let accounts = [
+            captured.keyed_account_for_clock_sysvar(),
+            captured.keyed_account_for_rent_sysvar(),
+            (Pubkey::new_unique(), Account::default()),
+        ];
+
+        let mut sysvars = Sysvars::default();
+        sysvars.set_from_accounts(&accounts);
+
+        assert_eq!(sysvars.clock.slot, 1_000);
+        assert_eq!(sysvars.clock, captured.clock);
+        assert_eq!(sysvars.rent, captured.rent);
+        // Sysvars without a provided account are left untouched.
+        assert_eq!(sysvars.slot_hashes, Sysvars::default().slot_hashes);
+    }
+
     #[test]
     fn test_to_sysvar_cache() {
         let clock = Clock {
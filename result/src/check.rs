@@ -5,10 +5,10 @@ use solana_account::AccountSharedData;
 use solana_transaction_status_client_types::InnerInstruction;
 use {
     crate::{
-        config::{compare, throw, CheckContext, Config},
+        config::{compare, throw, CheckContext, ComputeUnitsUpdate, Config},
         types::{InstructionResult, ProgramResult, TransactionProgramResult, TransactionResult},
     },
-    solana_account::ReadableAccount,
+    solana_account::{Account, ReadableAccount},
     solana_instruction::error::InstructionError,
     solana_program_error::ProgramError,
     solana_pubkey::Pubkey,
@@ -17,16 +17,29 @@ use {
 enum CheckType<'a> {
     /// Check the number of compute units consumed by the instruction.
     ComputeUnitsConsumed(u64),
+    /// Check that the instruction consumed any compute units at all.
+    SomeComputeUnitsConsumed,
     /// Check the time taken to execute the instruction.
     ExecutionTime(u64),
     /// Check the result code of the program's execution.
     ProgramResult(ProgramResult),
+    /// Check that the result code of the program's execution satisfies a
+    /// predicate.
+    ProgramResultMatches(Box<dyn Fn(&ProgramResult) -> bool + 'a>),
     /// Check the return data produced by executing the instruction.
     ReturnData(&'a [u8]),
+    /// Check that executing the instruction produced no return data.
+    NoReturnData,
     /// Check a resulting account after executing the instruction.
     ResultingAccount(AccountCheck<'a>),
-    /// Check that all accounts are rent exempt
-    AllRentExempt,
+    /// Check that a resulting account equals the provided account.
+    AccountEquals(Pubkey, &'a Account),
+    /// Check the number of resulting accounts.
+    ResultingAccountCount(usize),
+    /// Check that all accounts, except those listed, are rent exempt
+    AllRentExempt(&'a [Pubkey]),
+    /// Check that the total lamports across all accounts were conserved.
+    LamportsConserved,
     /// Check the number of inner instructions (CPIs) invoked.
     #[cfg(feature = "inner-instructions")]
     InnerInstructionCount(usize),
@@ -41,11 +54,47 @@ impl<'a> Check<'a> {
         Self { check }
     }
 
+    /// A short, human-readable label describing the check, for reporting.
+    pub fn label(&self) -> String {
+        match &self.check {
+            CheckType::ComputeUnitsConsumed(units) => format!("compute_units({units})"),
+            CheckType::SomeComputeUnitsConsumed => "consumed_compute_units".to_string(),
+            CheckType::ExecutionTime(time) => format!("execution_time({time})"),
+            CheckType::ProgramResult(result) => format!("program_result({result:?})"),
+            CheckType::ProgramResultMatches(_) => "program_result_matches".to_string(),
+            CheckType::ReturnData(_) => "return_data".to_string(),
+            CheckType::NoReturnData => "no_return_data".to_string(),
+            CheckType::ResultingAccount(account) => format!("account({})", account.pubkey),
+            CheckType::AccountEquals(pubkey, _) => format!("account_equals({pubkey})"),
+            CheckType::ResultingAccountCount(count) => format!("resulting_account_count({count})"),
+            CheckType::AllRentExempt(except) if except.is_empty() => "all_rent_exempt".to_string(),
+            CheckType::AllRentExempt(except) => format!("all_rent_exempt_except({})", except.len()),
+            CheckType::LamportsConserved => "lamports_conserved".to_string(),
+            #[cfg(feature = "inner-instructions")]
+            CheckType::InnerInstructionCount(count) => format!("inner_instruction_count({count})"),
+        }
+    }
+
     /// Check the number of compute units consumed by the instruction.
     pub const fn compute_units(units: u64) -> Self {
         Check::new(CheckType::ComputeUnitsConsumed(units))
     }
 
+    /// Assert that the instruction consumed no compute units.
+    ///
+    /// Equivalent to `Check::compute_units(0)`.
+    pub const fn no_compute_units() -> Self {
+        Check::compute_units(0)
+    }
+
+    /// Assert that the instruction consumed more than zero compute units,
+    /// ie. the program actually did work.
+    ///
+    /// Guards against mis-wired tests where the program never executed.
+    pub const fn consumed_compute_units() -> Self {
+        Check::new(CheckType::SomeComputeUnitsConsumed)
+    }
+
     /// Check the time taken to execute the instruction.
     pub const fn time(time: u64) -> Self {
         Check::new(CheckType::ExecutionTime(time))
@@ -71,19 +120,70 @@ impl<'a> Check<'a> {
         Check::new(CheckType::ProgramResult(result))
     }
 
+    /// Assert that the instruction's result satisfies the provided predicate,
+    /// for cases the fixed constructors don't cover, such as any failure, or
+    /// a custom error code within a range.
+    pub fn program_result_matches(f: impl Fn(&ProgramResult) -> bool + 'a) -> Self {
+        Check::new(CheckType::ProgramResultMatches(Box::new(f)))
+    }
+
     /// Check the return data produced by executing the instruction.
     pub const fn return_data(return_data: &'a [u8]) -> Self {
         Check::new(CheckType::ReturnData(return_data))
     }
 
+    /// Assert that executing the instruction produced no return data.
+    ///
+    /// Equivalent to `Check::return_data(&[])`, but reads more clearly in
+    /// tests asserting a program produced no output.
+    pub const fn no_return_data() -> Self {
+        Check::new(CheckType::NoReturnData)
+    }
+
     /// Check a resulting account after executing the instruction.
     pub const fn account(pubkey: &Pubkey) -> AccountCheckBuilder<'_> {
         AccountCheckBuilder::new(pubkey)
     }
 
+    /// Check that a resulting account equals the provided account: its
+    /// lamports, data, owner, executable flag, and rent epoch.
+    ///
+    /// On mismatch, every differing field is reported.
+    pub const fn account_equals(pubkey: &Pubkey, expected: &'a Account) -> Self {
+        Check::new(CheckType::AccountEquals(*pubkey, expected))
+    }
+
+    /// Check the number of resulting accounts.
+    ///
+    /// Note: Closed accounts remain in the resulting accounts, with their
+    /// state zeroed out, so closing an account doesn't change the count.
+    pub const fn resulting_account_count(count: usize) -> Self {
+        Check::new(CheckType::ResultingAccountCount(count))
+    }
+
     /// Check that all resulting accounts are rent exempt
     pub const fn all_rent_exempt() -> Self {
-        Check::new(CheckType::AllRentExempt)
+        Check::new(CheckType::AllRentExempt(&[]))
+    }
+
+    /// Check that all resulting accounts are rent exempt, except for those
+    /// with the provided keys, such as the incinerator or accounts being
+    /// closed.
+    pub const fn all_rent_exempt_except(except: &'a [Pubkey]) -> Self {
+        Check::new(CheckType::AllRentExempt(except))
+    }
+
+    /// Check that the total lamports across all resulting accounts equals the
+    /// total lamports across all input accounts.
+    ///
+    /// Lamports held by the incinerator are considered burned, and are
+    /// excluded from both totals.
+    ///
+    /// Note: This check requires the input accounts, so it can only be used
+    /// with `run_checks_with_inputs` or one of the `Mollusk` validation
+    /// methods.
+    pub const fn lamports_conserved() -> Self {
+        Check::new(CheckType::LamportsConserved)
     }
 
     /// Check the number of inner instructions (CPIs) invoked during execution.
@@ -95,6 +195,7 @@ impl<'a> Check<'a> {
 
 enum AccountStateCheck {
     Closed,
+    Defunded,
     RentExempt,
 }
 
@@ -102,11 +203,16 @@ struct AccountCheck<'a> {
     pubkey: Pubkey,
     check_data: Option<&'a [u8]>,
     check_executable: Option<bool>,
+    check_is_program: bool,
     check_lamports: Option<u64>,
     check_owner: Option<&'a Pubkey>,
+    check_owner_changed_to: Option<&'a Pubkey>,
     check_space: Option<usize>,
+    check_data_len_between: Option<(usize, usize)>,
+    check_data_all: Option<u8>,
     check_state: Option<AccountStateCheck>,
     check_data_slice: Option<(usize, &'a [u8])>,
+    check_pubkey_at: Option<(usize, &'a Pubkey)>,
 }
 
 impl AccountCheck<'_> {
@@ -115,11 +221,16 @@ impl AccountCheck<'_> {
             pubkey: *pubkey,
             check_data: None,
             check_executable: None,
+            check_is_program: false,
             check_lamports: None,
             check_owner: None,
+            check_owner_changed_to: None,
             check_space: None,
+            check_data_len_between: None,
+            check_data_all: None,
             check_state: None,
             check_data_slice: None,
+            check_pubkey_at: None,
         }
     }
 }
@@ -135,11 +246,24 @@ impl<'a> AccountCheckBuilder<'a> {
         }
     }
 
+    /// Assert that the account is closed: it has zero lamports, empty data,
+    /// and is owned by the system program, as the runtime leaves it.
+    ///
+    /// An account reallocated to zero data that still holds lamports, or one
+    /// drained of lamports but still assigned to a program, is not closed.
+    /// See `defunded` for the latter.
     pub const fn closed(mut self) -> Self {
         self.check.check_state = Some(AccountStateCheck::Closed);
         self
     }
 
+    /// Assert that the account holds zero lamports, regardless of its data
+    /// or owner.
+    pub const fn defunded(mut self) -> Self {
+        self.check.check_state = Some(AccountStateCheck::Defunded);
+        self
+    }
+
     pub const fn data(mut self, data: &'a [u8]) -> Self {
         self.check.check_data = Some(data);
         self
@@ -150,6 +274,13 @@ impl<'a> AccountCheckBuilder<'a> {
         self
     }
 
+    /// Assert that the account is a program: executable, and owned by one of
+    /// the known loaders.
+    pub const fn is_program(mut self) -> Self {
+        self.check.check_is_program = true;
+        self
+    }
+
     pub const fn lamports(mut self, lamports: u64) -> Self {
         self.check.check_lamports = Some(lamports);
         self
@@ -160,6 +291,19 @@ impl<'a> AccountCheckBuilder<'a> {
         self
     }
 
+    /// Assert that the account's owner changed to the provided owner.
+    ///
+    /// Fails if the owner is unchanged from the input account, or if the
+    /// resulting owner differs from the one provided.
+    ///
+    /// Note: This check requires the input accounts, so it can only be used
+    /// with `run_checks_with_inputs` or one of the `Mollusk` validation
+    /// methods.
+    pub const fn owner_changed_to(mut self, owner: &'a Pubkey) -> Self {
+        self.check.check_owner_changed_to = Some(owner);
+        self
+    }
+
     pub const fn rent_exempt(mut self) -> Self {
         self.check.check_state = Some(AccountStateCheck::RentExempt);
         self
@@ -170,148 +314,443 @@ impl<'a> AccountCheckBuilder<'a> {
         self
     }
 
+    /// Assert that the account's data length falls within `min..=max`.
+    pub const fn data_len_between(mut self, min: usize, max: usize) -> Self {
+        self.check.check_data_len_between = Some((min, max));
+        self
+    }
+
+    /// Assert that every byte of the account's data is zero, such as for a
+    /// freshly allocated account.
+    pub const fn data_zeroed(self) -> Self {
+        self.data_all(0)
+    }
+
+    /// Assert that every byte of the account's data equals the provided byte.
+    pub const fn data_all(mut self, byte: u8) -> Self {
+        self.check.check_data_all = Some(byte);
+        self
+    }
+
     pub const fn data_slice(mut self, offset: usize, data: &'a [u8]) -> Self {
         self.check.check_data_slice = Some((offset, data));
         self
     }
 
+    /// Assert that the 32 bytes of the account's data at the provided offset
+    /// equal the provided pubkey, such as an owner or delegate embedded in a
+    /// token account.
+    pub const fn pubkey_at(mut self, offset: usize, expected: &'a Pubkey) -> Self {
+        self.check.check_pubkey_at = Some((offset, expected));
+        self
+    }
+
     pub const fn build(self) -> Check<'a> {
         Check::new(CheckType::ResultingAccount(self.check))
     }
 }
 
-#[allow(clippy::too_many_arguments)]
+fn is_loader(owner: &Pubkey) -> bool {
+    [
+        solana_sdk_ids::bpf_loader_deprecated::id(),
+        solana_sdk_ids::bpf_loader::id(),
+        solana_sdk_ids::bpf_loader_upgradeable::id(),
+        solana_sdk_ids::loader_v4::id(),
+        solana_sdk_ids::native_loader::id(),
+    ]
+    .contains(owner)
+}
+
+#[derive(Clone, Copy)]
+struct CheckSubject<'a> {
+    compute_units_consumed: u64,
+    execution_time: u64,
+    program_result: &'a ProgramResult,
+    return_data: &'a [u8],
+    resulting_accounts: &'a [(Pubkey, AccountSharedData)],
+    input_accounts: Option<&'a [(Pubkey, AccountSharedData)]>,
+    #[cfg(feature = "inner-instructions")]
+    inner_instructions: &'a [InnerInstruction],
+}
+
 fn run_checks<C: CheckContext>(
     checks: &[Check],
     config: &Config,
     context: &C,
-    compute_units_consumed: u64,
-    execution_time: u64,
-    program_result: &ProgramResult,
-    return_data: &[u8],
-    resulting_accounts: &[(Pubkey, AccountSharedData)],
-    #[cfg(feature = "inner-instructions")] inner_instructions: &[InnerInstruction],
+    subject: &CheckSubject,
 ) -> bool {
-    let c = config;
     let mut pass = true;
     for check in checks {
-        match &check.check {
-            CheckType::ComputeUnitsConsumed(units) => {
-                let check_units = *units;
-                let actual_units = compute_units_consumed;
+        pass &= run_check(check, config, context, subject);
+    }
+    pass
+}
+
+fn run_check<C: CheckContext>(
+    check: &Check,
+    config: &Config,
+    context: &C,
+    subject: &CheckSubject,
+) -> bool {
+    let CheckSubject {
+        compute_units_consumed,
+        execution_time,
+        program_result,
+        return_data,
+        resulting_accounts,
+        input_accounts,
+        #[cfg(feature = "inner-instructions")]
+        inner_instructions,
+    } = *subject;
+    let c = config;
+    let mut pass = true;
+    match &check.check {
+        CheckType::ComputeUnitsConsumed(units) => {
+            let check_units = *units;
+            let actual_units = compute_units_consumed;
+            if c.update_compute_units && check_units != actual_units {
+                eprintln!(
+                    "    [MOLLUSK]: Updated compute units: `Check::compute_units({check_units})` \
+                     -> `Check::compute_units({actual_units})`"
+                );
+                c.compute_units_updates
+                    .lock()
+                    .unwrap()
+                    .push(ComputeUnitsUpdate {
+                        expected: check_units,
+                        actual: actual_units,
+                    });
+            } else {
                 pass &= compare!(c, "compute_units", check_units, actual_units);
             }
-            CheckType::ExecutionTime(time) => {
-                let check_time = *time;
-                let actual_time = execution_time;
-                pass &= compare!(c, "execution_time", check_time, actual_time);
+        }
+        CheckType::SomeComputeUnitsConsumed => {
+            if compute_units_consumed == 0 {
+                pass &= throw!(
+                    c,
+                    "CHECK FAILED: consumed_compute_units\n  Expected the instruction to consume \
+                     compute units, but it consumed none",
+                );
             }
-            CheckType::ProgramResult(check_program_result) => {
-                let check_result = check_program_result;
-                let actual_result = program_result;
-                pass &= compare!(c, "program_result", check_result, actual_result);
+        }
+        CheckType::ExecutionTime(time) => {
+            let check_time = *time;
+            let actual_time = execution_time;
+            pass &= compare!(c, "execution_time", check_time, actual_time);
+        }
+        CheckType::ProgramResult(check_program_result) => {
+            let check_result = check_program_result;
+            let actual_result = program_result;
+            pass &= compare!(c, "program_result", check_result, actual_result);
+        }
+        CheckType::ProgramResultMatches(predicate) => {
+            if !predicate(program_result) {
+                pass &= throw!(
+                    c,
+                    "CHECK FAILED: program_result_matches\n  Predicate not satisfied by: `{:?}`",
+                    program_result,
+                );
             }
-            CheckType::ReturnData(check_return_data) => {
-                let actual_return_data = return_data;
-                pass &= compare!(c, "return_data", *check_return_data, actual_return_data);
+        }
+        CheckType::ReturnData(check_return_data) => {
+            let actual_return_data = return_data;
+            pass &= compare!(c, "return_data", *check_return_data, actual_return_data);
+        }
+        CheckType::NoReturnData => {
+            if !return_data.is_empty() {
+                pass &= throw!(
+                    c,
+                    "Expected no return data, but got {} bytes: {:?}",
+                    return_data.len(),
+                    return_data
+                );
             }
-            CheckType::ResultingAccount(account) => {
-                let pubkey = account.pubkey;
-                let Some(resulting_account) = resulting_accounts
-                    .iter()
-                    .find(|(k, _)| k == &pubkey)
-                    .map(|(_, a)| a)
-                else {
-                    pass &= throw!(c, "Account not found in resulting accounts: {}", pubkey);
-                    continue;
-                };
-                if let Some(check_data) = account.check_data {
-                    let actual_data = resulting_account.data();
-                    pass &= compare!(c, "account_data", check_data, actual_data);
-                }
-                if let Some(check_executable) = account.check_executable {
-                    let actual_executable = resulting_account.executable();
-                    pass &= compare!(c, "account_executable", check_executable, actual_executable);
+        }
+        CheckType::ResultingAccount(account) => {
+            let pubkey = account.pubkey;
+            let Some(resulting_account) = resulting_accounts
+                .iter()
+                .find(|(k, _)| k == &pubkey)
+                .map(|(_, a)| a)
+            else {
+                pass &= throw!(c, "Account not found in resulting accounts: {}", pubkey);
+                return pass;
+            };
+            if let Some(check_data) = account.check_data {
+                let actual_data = resulting_account.data();
+                pass &= compare!(c, "account_data", check_data, actual_data);
+            }
+            if let Some(check_executable) = account.check_executable {
+                let actual_executable = resulting_account.executable();
+                pass &= compare!(c, "account_executable", check_executable, actual_executable);
+            }
+            if account.check_is_program {
+                let actual_executable = resulting_account.executable();
+                pass &= compare!(c, "account_is_program_executable", true, actual_executable);
+                let actual_owner = resulting_account.owner();
+                if !is_loader(actual_owner) {
+                    pass &= throw!(
+                        c,
+                        "CHECK FAILED: account_is_program_owner\n  Account {} is not owned by a \
+                         loader: `{}`",
+                        pubkey,
+                        actual_owner,
+                    );
                 }
-                if let Some(check_lamports) = account.check_lamports {
-                    let actual_lamports = resulting_account.lamports();
-                    pass &= compare!(c, "account_lamports", check_lamports, actual_lamports);
+            }
+            if let Some(check_lamports) = account.check_lamports {
+                let actual_lamports = resulting_account.lamports();
+                pass &= compare!(c, "account_lamports", check_lamports, actual_lamports);
+            }
+            if let Some(check_owner) = account.check_owner {
+                let actual_owner = resulting_account.owner();
+                pass &= compare!(c, "account_owner", check_owner, actual_owner);
+            }
+            if let Some(check_owner) = account.check_owner_changed_to {
+                let Some(input_account) = input_accounts.and_then(|input_accounts| {
+                    input_accounts
+                        .iter()
+                        .find(|(k, _)| k == &pubkey)
+                        .map(|(_, a)| a)
+                }) else {
+                    pass &= throw!(
+                        c,
+                        "Owner change check requires the input account for {}, see \
+                         `run_checks_with_inputs`",
+                        pubkey,
+                    );
+                    return pass;
+                };
+                let input_owner = input_account.owner();
+                let actual_owner = resulting_account.owner();
+                if input_owner == actual_owner {
+                    pass &= throw!(
+                        c,
+                        "CHECK FAILED: account_owner_changed\n  Owner of {} is unchanged: `{}`",
+                        pubkey,
+                        actual_owner,
+                    );
+                } else {
+                    pass &= compare!(c, "account_owner_changed_to", check_owner, actual_owner);
                 }
-                if let Some(check_owner) = account.check_owner {
-                    let actual_owner = resulting_account.owner();
-                    pass &= compare!(c, "account_owner", check_owner, actual_owner);
+            }
+            if let Some(check_space) = account.check_space {
+                let actual_space = resulting_account.data().len();
+                pass &= compare!(c, "account_space", check_space, actual_space);
+            }
+            if let Some((min, max)) = account.check_data_len_between {
+                let actual_len = resulting_account.data().len();
+                if actual_len < min || actual_len > max {
+                    pass &= throw!(
+                        c,
+                        "CHECK FAILED: account_data_len_between\n  Expected: `{}..={}`,\n Got: \
+                         `{}`",
+                        min,
+                        max,
+                        actual_len,
+                    );
                 }
-                if let Some(check_space) = account.check_space {
-                    let actual_space = resulting_account.data().len();
-                    pass &= compare!(c, "account_space", check_space, actual_space);
+            }
+            if let Some(check_byte) = account.check_data_all {
+                if let Some(offset) = resulting_account
+                    .data()
+                    .iter()
+                    .position(|byte| *byte != check_byte)
+                {
+                    pass &= throw!(
+                        c,
+                        "CHECK FAILED: account_data_all\n  Expected: every byte to be `{}`,\n \
+                         Got: `{}` at offset {}",
+                        check_byte,
+                        resulting_account.data()[offset],
+                        offset,
+                    );
                 }
-                if let Some(check_state) = &account.check_state {
-                    match check_state {
-                        AccountStateCheck::Closed => {
-                            pass &= compare!(
-                                c,
-                                "account_closed",
-                                true,
-                                resulting_account == &Default::default(),
-                            );
-                        }
-                        AccountStateCheck::RentExempt => {
-                            pass &= compare!(
-                                c,
-                                "account_rent_exempt",
-                                true,
-                                context.is_rent_exempt(
-                                    resulting_account.lamports(),
-                                    resulting_account.data().len(),
-                                    resulting_account.owner(),
-                                ),
-                            );
-                        }
+            }
+            if let Some(check_state) = &account.check_state {
+                match check_state {
+                    AccountStateCheck::Closed => {
+                        pass &= compare!(
+                            c,
+                            "account_closed_lamports",
+                            0,
+                            resulting_account.lamports(),
+                        );
+                        pass &= compare!(
+                            c,
+                            "account_closed_data_len",
+                            0,
+                            resulting_account.data().len(),
+                        );
+                        pass &= compare!(
+                            c,
+                            "account_closed_owner",
+                            &solana_sdk_ids::system_program::id(),
+                            resulting_account.owner(),
+                        );
                     }
-                }
-                if let Some((offset, check_data_slice)) = account.check_data_slice {
-                    let actual_data = resulting_account.data();
-                    if offset + check_data_slice.len() > actual_data.len() {
-                        pass &= throw!(
+                    AccountStateCheck::Defunded => {
+                        pass &= compare!(c, "account_defunded", 0, resulting_account.lamports(),);
+                    }
+                    AccountStateCheck::RentExempt => {
+                        pass &= compare!(
                             c,
-                            "Account data slice: offset {} + slice length {} exceeds account data \
-                             length {}",
-                            offset,
-                            check_data_slice.len(),
-                            actual_data.len(),
+                            "account_rent_exempt",
+                            true,
+                            context.is_rent_exempt(
+                                resulting_account.lamports(),
+                                resulting_account.data().len(),
+                                resulting_account.owner(),
+                            ),
                         );
-                        continue;
                     }
-                    let actual_data_slice = &actual_data[offset..offset + check_data_slice.len()];
-                    pass &= compare!(c, "account_data_slice", check_data_slice, actual_data_slice,);
                 }
             }
-            CheckType::AllRentExempt => {
-                for (pubkey, account) in resulting_accounts {
-                    let is_rent_exempt = context.is_rent_exempt(
+            if let Some((offset, check_data_slice)) = account.check_data_slice {
+                let actual_data = resulting_account.data();
+                if offset + check_data_slice.len() > actual_data.len() {
+                    pass &= throw!(
+                        c,
+                        "Account data slice: offset {} + slice length {} exceeds account data \
+                         length {}",
+                        offset,
+                        check_data_slice.len(),
+                        actual_data.len(),
+                    );
+                    return pass;
+                }
+                let actual_data_slice = &actual_data[offset..offset + check_data_slice.len()];
+                pass &= compare!(c, "account_data_slice", check_data_slice, actual_data_slice,);
+            }
+            if let Some((offset, check_pubkey)) = account.check_pubkey_at {
+                let actual_data = resulting_account.data();
+                let Some(actual_pubkey) = actual_data
+                    .get(offset..offset.saturating_add(32))
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .map(Pubkey::new_from_array)
+                else {
+                    pass &= throw!(
+                        c,
+                        "Account pubkey: offset {} + 32 exceeds account data length {}",
+                        offset,
+                        actual_data.len(),
+                    );
+                    return pass;
+                };
+                pass &= compare!(c, "account_pubkey_at", check_pubkey, &actual_pubkey);
+            }
+        }
+        CheckType::AccountEquals(pubkey, expected) => {
+            let Some(resulting_account) = resulting_accounts
+                .iter()
+                .find(|(k, _)| k == pubkey)
+                .map(|(_, a)| a)
+            else {
+                pass &= throw!(c, "Account not found in resulting accounts: {}", pubkey);
+                return pass;
+            };
+            let mut diff = String::new();
+            if expected.lamports != resulting_account.lamports() {
+                diff.push_str(&format!(
+                    "\n  lamports: expected `{}`, got `{}`",
+                    expected.lamports,
+                    resulting_account.lamports()
+                ));
+            }
+            if expected.data != resulting_account.data() {
+                diff.push_str(&format!(
+                    "\n  data: expected `{:?}`, got `{:?}`",
+                    expected.data,
+                    resulting_account.data()
+                ));
+            }
+            if &expected.owner != resulting_account.owner() {
+                diff.push_str(&format!(
+                    "\n  owner: expected `{}`, got `{}`",
+                    expected.owner,
+                    resulting_account.owner()
+                ));
+            }
+            if expected.executable != resulting_account.executable() {
+                diff.push_str(&format!(
+                    "\n  executable: expected `{}`, got `{}`",
+                    expected.executable,
+                    resulting_account.executable()
+                ));
+            }
+            if expected.rent_epoch != resulting_account.rent_epoch() {
+                diff.push_str(&format!(
+                    "\n  rent_epoch: expected `{}`, got `{}`",
+                    expected.rent_epoch,
+                    resulting_account.rent_epoch()
+                ));
+            }
+            if !diff.is_empty() {
+                pass &= throw!(c, "CHECK FAILED: account_equals ({}){}", pubkey, diff);
+            }
+        }
+        CheckType::ResultingAccountCount(count) => {
+            let check_count = *count;
+            let actual_count = resulting_accounts.len();
+            pass &= compare!(c, "resulting_account_count", check_count, actual_count);
+        }
+        CheckType::AllRentExempt(except) => {
+            for (pubkey, account) in resulting_accounts
+                .iter()
+                .filter(|(pubkey, _)| !except.contains(pubkey))
+            {
+                let is_rent_exempt = context.is_rent_exempt(
+                    account.lamports(),
+                    account.data().len(),
+                    account.owner(),
+                );
+                if !is_rent_exempt {
+                    pass &= throw!(
+                        c,
+                        "Account {} is not rent exempt after execution (lamports: {}, data_len: \
+                         {})",
+                        pubkey,
                         account.lamports(),
-                        account.data().len(),
-                        account.owner(),
+                        account.data().len()
                     );
-                    if !is_rent_exempt {
-                        pass &= throw!(
-                            c,
-                            "Account {} is not rent exempt after execution (lamports: {}, \
-                             data_len: {})",
-                            pubkey,
-                            account.lamports(),
-                            account.data().len()
-                        );
-                    }
                 }
             }
-            #[cfg(feature = "inner-instructions")]
-            CheckType::InnerInstructionCount(count) => {
-                let check_count = *count;
-                let actual_count = inner_instructions.len();
-                pass &= compare!(c, "inner_instruction_count", check_count, actual_count);
+        }
+        CheckType::LamportsConserved => {
+            let Some(input_accounts) = input_accounts else {
+                pass &= throw!(
+                    c,
+                    "Lamports conservation check requires the input accounts, see \
+                     `run_checks_with_inputs`"
+                );
+                return pass;
+            };
+            let total_lamports = |accounts: &[(Pubkey, AccountSharedData)]| {
+                accounts
+                    .iter()
+                    .filter(|(pubkey, _)| pubkey != &solana_sdk_ids::incinerator::id())
+                    .map(|(_, account)| i128::from(account.lamports()))
+                    .sum::<i128>()
+            };
+            let input_lamports = total_lamports(input_accounts);
+            let resulting_lamports = total_lamports(resulting_accounts);
+            if input_lamports != resulting_lamports {
+                pass &= throw!(
+                    c,
+                    "Lamports were not conserved: input total {}, resulting total {} (net \
+                     difference: {})",
+                    input_lamports,
+                    resulting_lamports,
+                    resulting_lamports - input_lamports,
+                );
             }
         }
+        #[cfg(feature = "inner-instructions")]
+        CheckType::InnerInstructionCount(count) => {
+            let check_count = *count;
+            let actual_count = inner_instructions.len();
+            pass &= compare!(c, "inner_instruction_count", check_count, actual_count);
+        }
     }
     pass
 }
@@ -328,18 +767,72 @@ impl InstructionResult {
         config: &Config,
         context: &C,
     ) -> bool {
-        run_checks(
-            checks,
-            config,
-            context,
-            self.compute_units_consumed,
-            self.execution_time,
-            &self.program_result,
-            &self.return_data,
-            &self.resulting_accounts,
+        self.run_checks_inner(checks, config, context, None)
+    }
+
+    /// Perform checks on the instruction result with a custom context, along
+    /// with the accounts provided as input to the instruction.
+    ///
+    /// Required for checks that compare resulting accounts against their
+    /// input state, such as `Check::lamports_conserved`.
+    pub fn run_checks_with_inputs<C: CheckContext>(
+        &self,
+        checks: &[Check],
+        config: &Config,
+        context: &C,
+        input_accounts: &[(Pubkey, AccountSharedData)],
+    ) -> bool {
+        self.run_checks_inner(checks, config, context, Some(input_accounts))
+    }
+
+    /// Perform checks on the instruction result with a custom context,
+    /// reporting the outcome of each check individually.
+    ///
+    /// Returns one entry per check, in order, pairing the check's label with
+    /// whether or not it passed. Every check is evaluated, regardless of
+    /// earlier failures, unless `config.panic` is set.
+    ///
+    /// The input accounts are required for checks that compare resulting
+    /// accounts against their input state, such as
+    /// `Check::lamports_conserved`.
+    pub fn run_checks_with_report<C: CheckContext>(
+        &self,
+        checks: &[Check],
+        config: &Config,
+        context: &C,
+        input_accounts: Option<&[(Pubkey, AccountSharedData)]>,
+    ) -> Vec<(String, bool)> {
+        let subject = self.check_subject(input_accounts);
+        checks
+            .iter()
+            .map(|check| (check.label(), run_check(check, config, context, &subject)))
+            .collect()
+    }
+
+    fn run_checks_inner<C: CheckContext>(
+        &self,
+        checks: &[Check],
+        config: &Config,
+        context: &C,
+        input_accounts: Option<&[(Pubkey, AccountSharedData)]>,
+    ) -> bool {
+        run_checks(checks, config, context, &self.check_subject(input_accounts))
+    }
+
+    fn check_subject<'a>(
+        &'a self,
+        input_accounts: Option<&'a [(Pubkey, AccountSharedData)]>,
+    ) -> CheckSubject<'a> {
+        CheckSubject {
+            compute_units_consumed: self.compute_units_consumed,
+            execution_time: self.execution_time,
+            program_result: &self.program_result,
+            return_data: &self.return_data,
+            resulting_accounts: &self.resulting_accounts,
+            input_accounts,
             #[cfg(feature = "inner-instructions")]
-            &self.inner_instructions,
-        )
+            inner_instructions: &self.inner_instructions,
+        }
     }
 }
 
@@ -354,6 +847,31 @@ impl TransactionResult {
         checks: &[Check],
         config: &Config,
         context: &C,
+    ) -> bool {
+        self.run_checks_inner(checks, config, context, None)
+    }
+
+    /// Perform checks on the transaction result with a custom context, along
+    /// with the accounts provided as input to the transaction.
+    ///
+    /// Required for checks that compare resulting accounts against their
+    /// input state, such as `Check::lamports_conserved`.
+    pub fn run_checks_with_inputs<C: CheckContext>(
+        &self,
+        checks: &[Check],
+        config: &Config,
+        context: &C,
+        input_accounts: &[(Pubkey, AccountSharedData)],
+    ) -> bool {
+        self.run_checks_inner(checks, config, context, Some(input_accounts))
+    }
+
+    fn run_checks_inner<C: CheckContext>(
+        &self,
+        checks: &[Check],
+        config: &Config,
+        context: &C,
+        input_accounts: Option<&[(Pubkey, AccountSharedData)]>,
     ) -> bool {
         let program_result = match &self.program_result {
             TransactionProgramResult::Success => ProgramResult::Success,
@@ -362,20 +880,509 @@ impl TransactionResult {
                 ProgramResult::UnknownError(err.clone())
             }
         };
-        run_checks(
-            checks,
-            config,
-            context,
-            self.compute_units_consumed,
-            self.execution_time,
-            &program_result,
-            &self.return_data,
-            &self.resulting_accounts,
+        let subject = CheckSubject {
+            compute_units_consumed: self.compute_units_consumed,
+            execution_time: self.execution_time,
+            program_result: &program_result,
+            return_data: &self.return_data,
+            resulting_accounts: &self.resulting_accounts,
+            input_accounts,
             #[cfg(feature = "inner-instructions")]
-            self.inner_instructions
+            inner_instructions: self
+                .inner_instructions
                 .first()
                 .map(Vec::as_slice)
                 .unwrap_or(&[]),
-        )
+        };
+        run_checks(checks, config, context, &subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_account::WritableAccount};
+
+    struct TestContext;
+
+    impl CheckContext for TestContext {}
+
+    #[test]
+    fn test_lamports_conserved() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        let checks = [Check::lamports_conserved()];
+
+        let sender = Pubkey::new_from_array([1; 32]);
+        let recipient = Pubkey::new_from_array([2; 32]);
+        let incinerator = solana_sdk_ids::incinerator::id();
+        let owner = Pubkey::default();
+
+        // A transfer moves lamports, but conserves them.
+        let input_accounts = vec![
+            (sender, AccountSharedData::new(100, 0, &owner)),
+            (recipient, AccountSharedData::new(0, 0, &owner)),
+        ];
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (sender, AccountSharedData::new(58, 0, &owner)),
+                (recipient, AccountSharedData::new(42, 0, &owner)),
+            ],
+            ..Default::default()
+        };
+        assert!(result.run_checks_with_inputs(&checks, &config, &TestContext, &input_accounts));
+
+        // Without inputs, the check can't be evaluated.
+        assert!(!result.run_checks(&checks, &config, &TestContext));
+
+        // Closing an account to the incinerator burns its lamports.
+        let input_accounts = vec![
+            (sender, AccountSharedData::new(100, 0, &owner)),
+            (incinerator, AccountSharedData::new(0, 0, &owner)),
+        ];
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (sender, AccountSharedData::default()),
+                (incinerator, AccountSharedData::new(100, 0, &owner)),
+            ],
+            ..Default::default()
+        };
+        assert!(!result.run_checks_with_inputs(&checks, &config, &TestContext, &input_accounts));
+    }
+
+    #[test]
+    fn test_data_all() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let zeroed = Pubkey::new_from_array([1; 32]);
+        let filled = Pubkey::new_from_array([2; 32]);
+        let written = Pubkey::new_from_array([3; 32]);
+        let owner = Pubkey::default();
+
+        let mut filled_account = AccountSharedData::new(0, 32, &owner);
+        filled_account.data_as_mut_slice().fill(0xff);
+        let mut written_account = AccountSharedData::new(0, 32, &owner);
+        written_account.data_as_mut_slice()[8..12].copy_from_slice(&[1, 2, 3, 4]);
+
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (zeroed, AccountSharedData::new(0, 32, &owner)),
+                (filled, filled_account),
+                (written, written_account),
+            ],
+            ..Default::default()
+        };
+
+        let pass = |check: Check| result.run_checks(&[check], &config, &TestContext);
+
+        assert!(pass(Check::account(&zeroed).data_zeroed().build()));
+        assert!(pass(Check::account(&filled).data_all(0xff).build()));
+        assert!(!pass(Check::account(&filled).data_zeroed().build()));
+        assert!(!pass(Check::account(&written).data_zeroed().build()));
+    }
+
+    #[test]
+    fn test_account_equals() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let owner = Pubkey::new_from_array([2; 32]);
+
+        let expected = Account {
+            lamports: 42,
+            data: vec![1, 2, 3, 4],
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let result = InstructionResult {
+            resulting_accounts: vec![(key, AccountSharedData::from(expected.clone()))],
+            ..Default::default()
+        };
+        assert!(result.run_checks(
+            &[Check::account_equals(&key, &expected)],
+            &config,
+            &TestContext
+        ));
+
+        let wrong = Account {
+            lamports: 43,
+            data: vec![1, 2, 3, 5],
+            ..expected.clone()
+        };
+        assert!(!result.run_checks(
+            &[Check::account_equals(&key, &wrong)],
+            &config,
+            &TestContext
+        ));
+
+        // Missing accounts fail as well.
+        assert!(!result.run_checks(
+            &[Check::account_equals(&owner, &expected)],
+            &config,
+            &TestContext
+        ));
+    }
+
+    #[test]
+    fn test_closed_and_defunded() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let closed = Pubkey::new_from_array([1; 32]);
+        let emptied = Pubkey::new_from_array([2; 32]);
+        let drained = Pubkey::new_from_array([3; 32]);
+        let program = Pubkey::new_from_array([4; 32]);
+
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (closed, AccountSharedData::default()),
+                // Reallocated to zero data, but still holding lamports.
+                (emptied, AccountSharedData::new(42, 0, &program)),
+                // Drained of lamports, but still assigned to a program.
+                (drained, AccountSharedData::new(0, 8, &program)),
+            ],
+            ..Default::default()
+        };
+        let run = |check: Check| result.run_checks(&[check], &config, &TestContext);
+
+        assert!(run(Check::account(&closed).closed().build()));
+        assert!(run(Check::account(&closed).defunded().build()));
+
+        assert!(!run(Check::account(&emptied).closed().build()));
+        assert!(!run(Check::account(&emptied).defunded().build()));
+
+        assert!(!run(Check::account(&drained).closed().build()));
+        assert!(run(Check::account(&drained).defunded().build()));
+    }
+
+    #[test]
+    fn test_program_result_matches() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        let any_failure =
+            || Check::program_result_matches(|result| matches!(result, ProgramResult::Failure(_)));
+
+        for error in [ProgramError::Custom(100), ProgramError::InvalidArgument] {
+            let result = InstructionResult {
+                program_result: ProgramResult::Failure(error),
+                ..Default::default()
+            };
+            assert!(result.run_checks(&[any_failure()], &config, &TestContext));
+        }
+
+        let result = InstructionResult::default();
+        assert!(!result.run_checks(&[any_failure()], &config, &TestContext));
+    }
+
+    #[test]
+    fn test_pubkey_at() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let delegate = Pubkey::new_from_array([2; 32]);
+        let other = Pubkey::new_from_array([3; 32]);
+
+        let mut account = AccountSharedData::new(0, 72, &Pubkey::default());
+        account.data_as_mut_slice()[40..72].copy_from_slice(delegate.as_ref());
+        let result = InstructionResult {
+            resulting_accounts: vec![(key, account)],
+            ..Default::default()
+        };
+        let run = |check: Check| result.run_checks(&[check], &config, &TestContext);
+
+        assert!(run(Check::account(&key).pubkey_at(40, &delegate).build()));
+        assert!(!run(Check::account(&key).pubkey_at(40, &other).build()));
+        assert!(!run(Check::account(&key).pubkey_at(8, &delegate).build()));
+        // Out of bounds.
+        assert!(!run(Check::account(&key).pubkey_at(41, &delegate).build()));
+        assert!(!run(Check::account(&key)
+            .pubkey_at(usize::MAX, &delegate)
+            .build()));
+    }
+
+    #[test]
+    fn test_no_return_data() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        let checks = [Check::no_return_data()];
+
+        let result = InstructionResult::default();
+        assert!(result.run_checks(&checks, &config, &TestContext));
+
+        let result = InstructionResult {
+            return_data: vec![0],
+            ..Default::default()
+        };
+        assert!(!result.run_checks(&checks, &config, &TestContext));
+    }
+
+    #[test]
+    fn test_no_and_consumed_compute_units() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let idle = InstructionResult::default();
+        let busy = InstructionResult {
+            compute_units_consumed: 150,
+            ..Default::default()
+        };
+
+        assert!(idle.run_checks(&[Check::no_compute_units()], &config, &TestContext));
+        assert!(!busy.run_checks(&[Check::no_compute_units()], &config, &TestContext));
+
+        assert!(!idle.run_checks(&[Check::consumed_compute_units()], &config, &TestContext));
+        assert!(busy.run_checks(&[Check::consumed_compute_units()], &config, &TestContext));
+    }
+
+    #[test]
+    fn test_update_compute_units() {
+        let result = InstructionResult {
+            compute_units_consumed: 150,
+            ..Default::default()
+        };
+        let checks = [Check::compute_units(100)];
+
+        // Update mode is opt-in.
+        assert!(!Config::default().update_compute_units);
+
+        let config = Config {
+            panic: false,
+            update_compute_units: false,
+            ..Default::default()
+        };
+        assert!(!result.run_checks(&checks, &config, &TestContext));
+        assert!(config.compute_units_updates.lock().unwrap().is_empty());
+
+        // In update mode, the observed value is accepted instead of failing,
+        // even when configured to panic, and reported.
+        let config = Config {
+            panic: true,
+            update_compute_units: true,
+            ..Default::default()
+        };
+        assert!(result.run_checks(&checks, &config, &TestContext));
+        assert_eq!(
+            *config.compute_units_updates.lock().unwrap(),
+            [ComputeUnitsUpdate {
+                expected: 100,
+                actual: 150,
+            }],
+        );
+
+        // Matching values aren't reported.
+        let checks = [Check::compute_units(150)];
+        assert!(result.run_checks(&checks, &config, &TestContext));
+        assert_eq!(config.compute_units_updates.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_resulting_account_count() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let owner = Pubkey::new_from_array([9; 32]);
+        let open = Pubkey::new_from_array([1; 32]);
+        let closing = Pubkey::new_from_array([2; 32]);
+
+        let before = InstructionResult {
+            resulting_accounts: vec![
+                (open, AccountSharedData::new(42, 0, &owner)),
+                (closing, AccountSharedData::new(42, 8, &owner)),
+            ],
+            ..Default::default()
+        };
+        assert!(before.run_checks(&[Check::resulting_account_count(2)], &config, &TestContext));
+
+        // The closed account is zeroed, but not removed.
+        let after = InstructionResult {
+            resulting_accounts: vec![
+                (open, AccountSharedData::new(84, 0, &owner)),
+                (closing, AccountSharedData::default()),
+            ],
+            ..Default::default()
+        };
+        assert!(after.run_checks(&[Check::resulting_account_count(2)], &config, &TestContext));
+        assert!(!after.run_checks(&[Check::resulting_account_count(1)], &config, &TestContext));
+    }
+
+    #[test]
+    fn test_all_rent_exempt_except() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let payer = Pubkey::new_from_array([1; 32]);
+        let closed = Pubkey::new_from_array([2; 32]);
+        let incinerator = solana_sdk_ids::incinerator::id();
+        let program_owner = Pubkey::new_from_array([3; 32]);
+
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (
+                    payer,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (closed, AccountSharedData::new(0, 0, &program_owner)),
+                (
+                    incinerator,
+                    AccountSharedData::new(42, 0, &Pubkey::default()),
+                ),
+            ],
+            ..Default::default()
+        };
+
+        assert!(!result.run_checks(&[Check::all_rent_exempt()], &config, &TestContext));
+        assert!(!result.run_checks(
+            &[Check::all_rent_exempt_except(&[closed])],
+            &config,
+            &TestContext,
+        ));
+        assert!(result.run_checks(
+            &[Check::all_rent_exempt_except(&[closed, incinerator])],
+            &config,
+            &TestContext,
+        ));
+    }
+
+    #[test]
+    fn test_run_checks_with_report() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let result = InstructionResult {
+            compute_units_consumed: 42,
+            resulting_accounts: vec![(key, AccountSharedData::new(42, 0, &Pubkey::default()))],
+            ..Default::default()
+        };
+
+        let checks = [
+            Check::success(),
+            Check::compute_units(41),
+            Check::account(&key).lamports(42).build(),
+            Check::account(&key).lamports(41).build(),
+            Check::lamports_conserved(),
+        ];
+        let report = result.run_checks_with_report(&checks, &config, &TestContext, None);
+
+        assert_eq!(
+            report,
+            vec![
+                ("program_result(Success)".to_string(), true),
+                ("compute_units(41)".to_string(), false),
+                (format!("account({key})"), true),
+                (format!("account({key})"), false),
+                ("lamports_conserved".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data_len_between() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let result = InstructionResult {
+            resulting_accounts: vec![(key, AccountSharedData::new(0, 64, &Pubkey::default()))],
+            ..Default::default()
+        };
+
+        let pass = |min, max| {
+            let checks = [Check::account(&key).data_len_between(min, max).build()];
+            result.run_checks(&checks, &config, &TestContext)
+        };
+
+        assert!(pass(32, 128));
+        assert!(pass(64, 64));
+        assert!(!pass(0, 63));
+        assert!(!pass(65, 128));
+    }
+
+    #[test]
+    fn test_is_program() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let checks = [Check::account(&key).is_program().build()];
+
+        let pass = |owner: &Pubkey, executable: bool| {
+            let mut account = AccountSharedData::new(1, 0, owner);
+            account.set_executable(executable);
+            let result = InstructionResult {
+                resulting_accounts: vec![(key, account)],
+                ..Default::default()
+            };
+            result.run_checks(&checks, &config, &TestContext)
+        };
+
+        assert!(pass(&solana_sdk_ids::bpf_loader_upgradeable::id(), true));
+        assert!(pass(&solana_sdk_ids::loader_v4::id(), true));
+        assert!(!pass(&solana_sdk_ids::bpf_loader_upgradeable::id(), false));
+        assert!(!pass(&Pubkey::new_from_array([2; 32]), true));
+    }
+
+    #[test]
+    fn test_owner_changed_to() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_from_array([1; 32]);
+        let system_program = Pubkey::default();
+        let new_owner = Pubkey::new_from_array([2; 32]);
+        let other_owner = Pubkey::new_from_array([3; 32]);
+
+        // Mirrors a system `assign` instruction.
+        let input_accounts = vec![(key, AccountSharedData::new(0, 0, &system_program))];
+        let result = InstructionResult {
+            resulting_accounts: vec![(key, AccountSharedData::new(0, 0, &new_owner))],
+            ..Default::default()
+        };
+
+        let pass = |owner: &Pubkey, input_accounts: &[(Pubkey, AccountSharedData)]| {
+            let checks = [Check::account(&key).owner_changed_to(owner).build()];
+            result.run_checks_with_inputs(&checks, &config, &TestContext, input_accounts)
+        };
+
+        assert!(pass(&new_owner, &input_accounts));
+        assert!(!pass(&other_owner, &input_accounts));
+
+        // Unchanged owner.
+        let input_accounts = vec![(key, AccountSharedData::new(0, 0, &new_owner))];
+        assert!(!pass(&new_owner, &input_accounts));
+
+        // Missing input account.
+        assert!(!pass(&new_owner, &[]));
     }
 }
@@ -2,11 +2,13 @@
 
 use {
     crate::{
-        config::{compare, Config},
-        types::InstructionResult,
+        config::{compare, throw, Config},
+        types::{InstructionResult, ProgramResult},
     },
     solana_account::ReadableAccount,
+    solana_program_error::ProgramError,
     solana_pubkey::Pubkey,
+    std::ops::Range,
 };
 
 /// Checks to run between two `InstructionResult` instances.
@@ -25,6 +27,9 @@ pub enum Compare {
     ComputeUnits,
     /// Validate execution time.
     ExecutionTime,
+    /// Validate execution time, allowing the two results to differ by up to
+    /// the provided number of microseconds.
+    ExecutionTimeWithin(u64),
     /// Validate the program result.
     ProgramResult,
     /// Validate the return data.
@@ -77,6 +82,13 @@ pub enum Compare {
         /// space.
         space: bool,
     },
+    /// Validate all resulting accounts, zeroing the provided byte ranges of
+    /// each listed account's data before comparing.
+    ///
+    /// Useful for account layouts embedding values that legitimately vary,
+    /// such as a timestamp or slot. Ranges extending past the end of an
+    /// account's data are truncated.
+    AccountsIgnoringRanges(Vec<(Pubkey, Range<usize>)>),
 }
 
 impl Compare {
@@ -127,9 +139,10 @@ impl Compare {
     }
 
     /// Validate everything but compute unit consumption.
+    ///
+    /// Note: Like `everything`, this does not validate execution time.
     pub fn everything_but_cus() -> Vec<Self> {
         vec![
-            // Self::ExecutionTime, // TODO: Intentionally omitted for now...
             Self::ProgramResult,
             Self::ReturnData,
             Self::all_resulting_accounts(),
@@ -137,10 +150,13 @@ impl Compare {
     }
 
     /// Validate everything.
+    ///
+    /// Note: Execution time is intentionally excluded, since it varies between
+    /// runs and machines, and would make comparisons flaky. To compare it, add
+    /// `Compare::ExecutionTimeWithin` with a suitable tolerance.
     pub fn everything() -> Vec<Self> {
         vec![
             Self::ComputeUnits,
-            // Self::ExecutionTime, // TODO: Intentionally omitted for now...
             Self::ProgramResult,
             Self::ReturnData,
             Self::all_resulting_accounts(),
@@ -162,19 +178,59 @@ impl InstructionResult {
         b: &Self,
         addresses: &[Pubkey],
         ignore_addresses: &[Pubkey],
+        ignore_ranges: &[(Pubkey, Range<usize>)],
         fields: CompareAccountFields,
         config: &Config,
     ) -> bool {
         let c = config;
         let mut pass = true;
-        for (a, b) in self
-            .resulting_accounts
-            .iter()
-            .zip(b.resulting_accounts.iter())
-        {
+        let pairs: Vec<_> = if c.match_accounts_by_key {
+            let mut pairs = Vec::with_capacity(self.resulting_accounts.len());
+            for a in self.resulting_accounts.iter() {
+                if !addresses.contains(&a.0) || ignore_addresses.contains(&a.0) {
+                    continue;
+                }
+                match b.resulting_accounts.iter().find(|(k, _)| k == &a.0) {
+                    Some(b) => pairs.push((a, b)),
+                    None => {
+                        pass &= throw!(
+                            c,
+                            "CHECK FAILED: resulting_account\n  Account `{}` is missing from the \
+                             compared result",
+                            a.0,
+                        );
+                    }
+                }
+            }
+            pairs
+        } else {
+            self.resulting_accounts
+                .iter()
+                .zip(b.resulting_accounts.iter())
+                .collect()
+        };
+        for (a, b) in pairs {
             if addresses.contains(&a.0) && !ignore_addresses.contains(&a.0) {
                 if fields.data {
-                    pass &= compare!(c, "resulting_account_data", a.1.data(), b.1.data());
+                    if ignore_ranges.iter().any(|(k, _)| k == &a.0) {
+                        let mask = |data: &[u8]| {
+                            let mut data = data.to_vec();
+                            for (_, range) in ignore_ranges.iter().filter(|(k, _)| k == &a.0) {
+                                let end = range.end.min(data.len());
+                                let start = range.start.min(end);
+                                data[start..end].fill(0);
+                            }
+                            data
+                        };
+                        pass &= compare!(
+                            c,
+                            "resulting_account_data",
+                            mask(a.1.data()),
+                            mask(b.1.data())
+                        );
+                    } else {
+                        pass &= compare!(c, "resulting_account_data", a.1.data(), b.1.data());
+                    }
                 }
                 if fields.executable {
                     pass &= compare!(
@@ -225,6 +281,28 @@ impl InstructionResult {
                 Compare::ExecutionTime => {
                     pass &= compare!(c, "execution_time", self.execution_time, b.execution_time);
                 }
+                Compare::ExecutionTimeWithin(tolerance) => {
+                    let difference = self.execution_time.abs_diff(b.execution_time);
+                    if difference > *tolerance {
+                        pass &= throw!(
+                            c,
+                            "CHECK FAILED: execution_time_within\n  Expected: `{}` and `{}` to be \
+                             within `{}`,\n Got difference: `{}`",
+                            self.execution_time,
+                            b.execution_time,
+                            tolerance,
+                            difference,
+                        );
+                    }
+                }
+                Compare::ProgramResult if c.normalize_errors => {
+                    pass &= compare!(
+                        c,
+                        "program_result",
+                        normalize_program_result(&self.program_result),
+                        normalize_program_result(&b.program_result)
+                    );
+                }
                 Compare::ProgramResult => {
                     pass &= compare!(c, "program_result", self.program_result, b.program_result);
                 }
@@ -253,6 +331,7 @@ impl InstructionResult {
                         b,
                         &addresses,
                         &[],
+                        &[],
                         CompareAccountFields {
                             data: *data,
                             executable: *executable,
@@ -275,6 +354,7 @@ impl InstructionResult {
                         b,
                         addresses,
                         &[],
+                        &[],
                         CompareAccountFields {
                             data: *data,
                             executable: *executable,
@@ -302,6 +382,7 @@ impl InstructionResult {
                         b,
                         &addresses,
                         ignore_addresses,
+                        &[],
                         CompareAccountFields {
                             data: *data,
                             executable: *executable,
@@ -312,6 +393,33 @@ impl InstructionResult {
                         c,
                     );
                 }
+                Compare::AccountsIgnoringRanges(ignore_ranges) => {
+                    pass &= compare!(
+                        c,
+                        "resulting_accounts_length",
+                        self.resulting_accounts.len(),
+                        b.resulting_accounts.len()
+                    );
+                    let addresses = self
+                        .resulting_accounts
+                        .iter()
+                        .map(|(k, _)| *k)
+                        .collect::<Vec<_>>();
+                    pass &= self.compare_resulting_accounts(
+                        b,
+                        &addresses,
+                        &[],
+                        ignore_ranges,
+                        CompareAccountFields {
+                            data: true,
+                            executable: true,
+                            lamports: true,
+                            owner: true,
+                            space: true,
+                        },
+                        c,
+                    );
+                }
             }
         }
         pass
@@ -326,7 +434,162 @@ impl InstructionResult {
             &Config {
                 panic: true,
                 verbose: true,
+                ..Default::default()
             },
         );
     }
 }
+
+/// Map an `UnknownError` to a `Failure` wherever its `InstructionError` has
+/// a `ProgramError` representation.
+fn normalize_program_result(result: &ProgramResult) -> ProgramResult {
+    match result {
+        ProgramResult::UnknownError(err) => ProgramError::try_from(err.clone())
+            .map(ProgramResult::Failure)
+            .unwrap_or_else(|_| result.clone()),
+        _ => result.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, solana_account::AccountSharedData, solana_instruction::error::InstructionError,
+    };
+
+    #[test]
+    fn test_compare_normalize_errors() {
+        let a = InstructionResult {
+            program_result: ProgramResult::Failure(ProgramError::Custom(1)),
+            ..Default::default()
+        };
+        let b = InstructionResult {
+            program_result: ProgramResult::UnknownError(InstructionError::Custom(1)),
+            ..Default::default()
+        };
+        let c = InstructionResult {
+            program_result: ProgramResult::UnknownError(InstructionError::Custom(2)),
+            ..Default::default()
+        };
+
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        assert!(!a.compare_with_config(&b, &[Compare::ProgramResult], &config));
+
+        let config = Config {
+            panic: false,
+            normalize_errors: true,
+            ..Default::default()
+        };
+        assert!(a.compare_with_config(&b, &[Compare::ProgramResult], &config));
+        assert!(b.compare_with_config(&a, &[Compare::ProgramResult], &config));
+        assert!(!a.compare_with_config(&c, &[Compare::ProgramResult], &config));
+    }
+
+    #[test]
+    fn test_compare_execution_time() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let a = InstructionResult {
+            execution_time: 100,
+            ..Default::default()
+        };
+        let b = InstructionResult {
+            execution_time: 142,
+            ..Default::default()
+        };
+
+        // Execution time is not part of `everything`.
+        assert!(a.compare_with_config(&b, &Compare::everything(), &config));
+        assert!(a.compare_with_config(&b, &Compare::everything_but_cus(), &config));
+
+        assert!(!a.compare_with_config(&b, &[Compare::ExecutionTime], &config));
+        assert!(a.compare_with_config(&b, &[Compare::ExecutionTimeWithin(42)], &config));
+        assert!(b.compare_with_config(&a, &[Compare::ExecutionTimeWithin(42)], &config));
+        assert!(!a.compare_with_config(&b, &[Compare::ExecutionTimeWithin(41)], &config));
+    }
+
+    #[test]
+    fn test_compare_match_accounts_by_key() {
+        let key_a = Pubkey::new_unique();
+        let key_b = Pubkey::new_unique();
+        let account_a = AccountSharedData::new(42, 0, &Pubkey::default());
+        let account_b = AccountSharedData::new(84, 8, &Pubkey::default());
+
+        let live = InstructionResult {
+            resulting_accounts: vec![(key_a, account_a.clone()), (key_b, account_b.clone())],
+            ..Default::default()
+        };
+        // Fixture effects listing the same accounts in a different order.
+        let fixture = InstructionResult {
+            resulting_accounts: vec![(key_b, account_b), (key_a, account_a)],
+            ..Default::default()
+        };
+
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+        assert!(!live.compare_with_config(&fixture, &Compare::everything(), &config));
+
+        let config = Config {
+            panic: false,
+            match_accounts_by_key: true,
+            ..Default::default()
+        };
+        assert!(live.compare_with_config(&fixture, &Compare::everything(), &config));
+    }
+
+    #[test]
+    fn test_compare_accounts_ignoring_ranges() {
+        let config = Config {
+            panic: false,
+            ..Default::default()
+        };
+
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account_with_data = |data: &[u8]| {
+            let mut account = AccountSharedData::new(42, data.len(), &owner);
+            account.set_data_from_slice(data);
+            account
+        };
+
+        // The two results differ only in bytes 4..8, such as an embedded slot.
+        let a = InstructionResult {
+            resulting_accounts: vec![(key, account_with_data(&[1, 2, 3, 4, 5, 6, 7, 8, 9]))],
+            ..Default::default()
+        };
+        let b = InstructionResult {
+            resulting_accounts: vec![(key, account_with_data(&[1, 2, 3, 4, 0, 0, 0, 0, 9]))],
+            ..Default::default()
+        };
+
+        assert!(!a.compare_with_config(&b, &[Compare::all_resulting_accounts()], &config));
+        assert!(a.compare_with_config(
+            &b,
+            &[Compare::AccountsIgnoringRanges(vec![(key, 4..8)])],
+            &config
+        ));
+        // Bytes outside the ignored range are still compared.
+        assert!(!a.compare_with_config(
+            &b,
+            &[Compare::AccountsIgnoringRanges(vec![(key, 4..6)])],
+            &config
+        ));
+        // Ranges past the end of the data are truncated.
+        assert!(a.compare_with_config(
+            &b,
+            &[Compare::AccountsIgnoringRanges(vec![
+                (key, 4..8),
+                (key, 8..100)
+            ])],
+            &config
+        ));
+    }
+}
@@ -1,10 +1,96 @@
 //! Configuration and context for result validation.
 
-use {solana_pubkey::Pubkey, solana_rent::Rent};
+use {
+    solana_pubkey::Pubkey,
+    solana_rent::Rent,
+    std::sync::{Arc, Mutex},
+};
 
+/// A compute units value accepted in update mode. See
+/// `Config::update_compute_units`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComputeUnitsUpdate {
+    /// The value passed to `Check::compute_units`.
+    pub expected: u64,
+    /// The compute units actually consumed.
+    pub actual: u64,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub panic: bool,
     pub verbose: bool,
+    /// When an instruction fails, read the resulting accounts from the
+    /// transaction context anyway, rather than returning the input accounts.
+    ///
+    /// Useful for inspecting the state at the point of failure. Note this is
+    /// not what the runtime would commit: on-chain, a failed transaction's
+    /// account changes are always discarded.
+    pub capture_accounts_on_failure: bool,
+    /// Print a warning to stderr whenever the accounts provided to the
+    /// harness contain the same pubkey more than once.
+    ///
+    /// Mollusk silently uses the first occurrence of a duplicated account,
+    /// which can mask mistakes in test setup.
+    pub warn_on_duplicate_accounts: bool,
+    /// Instead of failing on a compute units mismatch, accept the observed
+    /// value, so hardcoded `Check::compute_units` values can be refreshed
+    /// after a toolchain change. Each update is printed to stderr and
+    /// recorded in `compute_units_updates`.
+    ///
+    /// Defaults to `false`. The harness enables it when the
+    /// `MOLLUSK_UPDATE_CU` environment variable is set.
+    pub update_compute_units: bool,
+    /// Every compute units mismatch accepted under `update_compute_units`, in
+    /// the order the checks ran.
+    ///
+    /// Clones of a config share the same collection, so the new values can
+    /// be listed once a test is done.
+    pub compute_units_updates: Arc<Mutex<Vec<ComputeUnitsUpdate>>>,
+    /// When comparing resulting accounts with `Compare`, pair accounts by
+    /// pubkey rather than by position.
+    ///
+    /// Useful when comparing against results loaded from fixtures, which may
+    /// list the resulting accounts in a different order than the harness.
+    pub match_accounts_by_key: bool,
+    /// Reject any instruction whose data is longer than this many bytes with
+    /// `InstructionError::InvalidInstructionData`, before it reaches the
+    /// program.
+    ///
+    /// Applies to every top-level instruction, whether processed on its own,
+    /// in a chain, in a transaction, or raw. Useful for catching tests that
+    /// accidentally rely on unbounded instruction data.
+    pub max_instruction_data_len: Option<usize>,
+    /// Collect the resulting accounts after processing an instruction.
+    ///
+    /// Disabling this leaves `resulting_accounts` empty, skipping a clone of
+    /// every account, which is a meaningful speedup for benching or fuzzing
+    /// where they go unused. Account checks can't pass without them. Chains
+    /// carry state between steps through the resulting accounts, so they
+    /// always collect them.
+    pub collect_resulting_accounts: bool,
+    /// Before processing an instruction, top up the lamports of its first
+    /// signer to at least this amount.
+    ///
+    /// The lamports are created from thin air, which is unrealistic and only
+    /// meant for test convenience: it saves funding a payer just to cover
+    /// rent on the accounts it creates. Applies to instructions processed on
+    /// their own or in a chain, where each step's first signer is topped up.
+    ///
+    /// Per-instruction checks comparing against input state, such as
+    /// `Check::lamports_conserved`, compare against the funded inputs. Checks
+    /// spanning a whole chain, such as composite checks, compare against the
+    /// accounts originally provided, so they will see the minted lamports. If
+    /// the instruction fails, the minted lamports are discarded along with
+    /// every other change, unless `capture_accounts_on_failure` is set.
+    pub auto_fund_payer: Option<u64>,
+    /// When comparing program results with `Compare::ProgramResult`, treat
+    /// `ProgramResult::Failure` and `ProgramResult::UnknownError` as equal
+    /// if they represent the same underlying `InstructionError`.
+    ///
+    /// Useful when comparing against results from another source, such as a
+    /// fixture, which may not have mapped the error to a `ProgramError`.
+    pub normalize_errors: bool,
 }
 
 impl Default for Config {
@@ -12,6 +98,15 @@ impl Default for Config {
         Self {
             panic: true,
             verbose: false,
+            capture_accounts_on_failure: false,
+            warn_on_duplicate_accounts: false,
+            update_compute_units: false,
+            compute_units_updates: Arc::default(),
+            match_accounts_by_key: false,
+            max_instruction_data_len: None,
+            collect_resulting_accounts: true,
+            auto_fund_payer: None,
+            normalize_errors: false,
         }
     }
 }
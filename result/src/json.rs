@@ -0,0 +1,149 @@
+//! JSON serialization of results, for caching runs and for interop with
+//! non-Rust tooling.
+//!
+//! Pubkeys are encoded as base58 strings, account data as base64, and program
+//! errors as their numeric code, so the output is stable across versions.
+
+use {
+    crate::types::InstructionResult,
+    base64::{prelude::BASE64_STANDARD, Engine},
+    serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer},
+    solana_account::{Account, AccountSharedData, ReadableAccount},
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+impl InstructionResult {
+    /// Serialize the result to JSON.
+    ///
+    /// Note: Inner instructions and the compiled message are not serialized,
+    /// and will be empty when deserialized.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Deserialize a result from JSON produced by `to_json`.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyedAccount {
+    pubkey: String,
+    lamports: u64,
+    data: String,
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+}
+
+pub(crate) mod keyed_accounts {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        accounts: &[(Pubkey, AccountSharedData)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        accounts
+            .iter()
+            .map(|(pubkey, account)| KeyedAccount {
+                pubkey: pubkey.to_string(),
+                lamports: account.lamports(),
+                data: BASE64_STANDARD.encode(account.data()),
+                owner: account.owner().to_string(),
+                executable: account.executable(),
+                rent_epoch: account.rent_epoch(),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(Pubkey, AccountSharedData)>, D::Error> {
+        Vec::<KeyedAccount>::deserialize(deserializer)?
+            .into_iter()
+            .map(|keyed_account| {
+                let pubkey = Pubkey::from_str(&keyed_account.pubkey).map_err(D::Error::custom)?;
+                let account = Account {
+                    lamports: keyed_account.lamports,
+                    data: BASE64_STANDARD
+                        .decode(&keyed_account.data)
+                        .map_err(D::Error::custom)?,
+                    owner: Pubkey::from_str(&keyed_account.owner).map_err(D::Error::custom)?,
+                    executable: keyed_account.executable,
+                    rent_epoch: keyed_account.rent_epoch,
+                };
+                Ok((pubkey, account.into()))
+            })
+            .collect()
+    }
+}
+
+pub(crate) mod program_error {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        error: &ProgramError,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        u64::from(error.clone()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ProgramError, D::Error> {
+        u64::deserialize(deserializer).map(ProgramError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_instruction::error::InstructionError};
+
+    #[test]
+    fn test_json_round_trip_success() {
+        let result = InstructionResult {
+            compute_units_consumed: 150,
+            execution_time: 12,
+            return_data: vec![1, 2, 3],
+            resulting_accounts: vec![(
+                Pubkey::new_from_array([1; 32]),
+                Account {
+                    lamports: 42,
+                    data: vec![0, 1, 2, 255],
+                    owner: Pubkey::new_from_array([2; 32]),
+                    executable: false,
+                    rent_epoch: u64::MAX,
+                }
+                .into(),
+            )],
+            ..Default::default()
+        };
+
+        let json = result.to_json().unwrap();
+        assert_eq!(InstructionResult::from_json(&json).unwrap(), result);
+    }
+
+    #[test]
+    fn test_json_round_trip_error() {
+        for raw_result in [
+            Err(InstructionError::Custom(0)),
+            Err(InstructionError::Custom(42)),
+            Err(InstructionError::InvalidAccountData),
+            Err(InstructionError::ComputationalBudgetExceeded),
+        ] {
+            let result = InstructionResult {
+                program_result: raw_result.clone().into(),
+                raw_result,
+                ..Default::default()
+            };
+
+            let json = result.to_json().unwrap();
+            assert_eq!(InstructionResult::from_json(&json).unwrap(), result);
+        }
+    }
+}
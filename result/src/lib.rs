@@ -31,6 +31,8 @@
 pub mod check;
 pub mod compare;
 pub mod config;
+#[cfg(feature = "serde")]
+mod json;
 pub mod types;
 
 // Re-export the main types and traits for convenience, and for backwards
@@ -39,5 +41,5 @@ pub use {
     check::{AccountCheckBuilder, Check},
     compare::Compare,
     config::{CheckContext, Config},
-    types::{InstructionResult, ProgramResult},
+    types::{ChainResult, ContextResult, InstructionResult, ProgramResult},
 };
@@ -1,20 +1,30 @@
 //! Core result types for SVM program execution.
 
 use {
-    solana_account::AccountSharedData, solana_instruction::error::InstructionError,
-    solana_program_error::ProgramError, solana_pubkey::Pubkey,
+    solana_account::{AccountSharedData, ReadableAccount},
+    solana_instruction::error::InstructionError,
+    solana_program_error::ProgramError,
+    solana_pubkey::Pubkey,
     solana_transaction_error::TransactionError,
 };
 #[cfg(feature = "inner-instructions")]
 use {solana_message::SanitizedMessage, solana_transaction_status_client_types::InnerInstruction};
+#[cfg(feature = "detailed-timings")]
+use {
+    solana_svm_timings::ExecuteTimings,
+    std::{ops::Deref, sync::Arc},
+};
 
 /// The result code of the program's execution.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProgramResult {
     /// The program executed successfully.
     Success,
     /// The program returned an error.
-    Failure(ProgramError),
+    Failure(
+        #[cfg_attr(feature = "serde", serde(with = "crate::json::program_error"))] ProgramError,
+    ),
     /// Mollusk encountered an error while executing the program.
     UnknownError(InstructionError),
 }
@@ -46,11 +56,59 @@ impl From<Result<(), InstructionError>> for ProgramResult {
     }
 }
 
+/// The full execution timings collected by the runtime, such as those for
+/// serialization and verification.
+///
+/// Timings vary between runs, so they're ignored when comparing results for
+/// equality: any two `DetailedTimings` are equal.
+#[cfg(feature = "detailed-timings")]
+#[derive(Clone, Debug, Default)]
+pub struct DetailedTimings(pub Arc<ExecuteTimings>);
+
+#[cfg(feature = "detailed-timings")]
+impl From<ExecuteTimings> for DetailedTimings {
+    fn from(timings: ExecuteTimings) -> Self {
+        Self(Arc::new(timings))
+    }
+}
+
+#[cfg(feature = "detailed-timings")]
+impl Deref for DetailedTimings {
+    type Target = ExecuteTimings;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "detailed-timings")]
+impl PartialEq for DetailedTimings {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "detailed-timings")]
+impl Eq for DetailedTimings {}
+
 /// The overall result of the instruction.
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct InstructionResult {
     /// The number of compute units consumed by the instruction.
     pub compute_units_consumed: u64,
+    /// The portion of `compute_units_consumed` spent in instructions whose
+    /// top-level program is a builtin (or precompile).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub builtin_compute_units: u64,
+    /// The portion of `compute_units_consumed` spent in instructions whose
+    /// top-level program is a BPF program, including any CPIs it makes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bpf_compute_units: u64,
     /// The time taken to execute the instruction.
     pub execution_time: u64,
     /// The result code of the program's execution.
@@ -64,6 +122,7 @@ pub struct InstructionResult {
     /// This includes all accounts provided to the processor, in the order
     /// they were provided. Any accounts that were modified will maintain
     /// their original position in this list, but with updated state.
+    #[cfg_attr(feature = "serde", serde(with = "crate::json::keyed_accounts"))]
     pub resulting_accounts: Vec<(Pubkey, AccountSharedData)>,
     /// Inner instructions (CPIs) invoked during the instruction execution.
     ///
@@ -71,6 +130,7 @@ pub struct InstructionResult {
     /// including the invoked instruction and the stack height at which it
     /// was called.
     #[cfg(feature = "inner-instructions")]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub inner_instructions: Vec<InnerInstruction>,
     /// The compiled message used to execute the instruction.
     ///
@@ -78,15 +138,28 @@ pub struct InstructionResult {
     /// their corresponding pubkeys via `message.account_keys()`.
     ///
     /// This is `None` when the result is loaded from a fuzz fixture, since
-    /// fixtures don't contain the compiled message.
+    /// fixtures don't contain the compiled message, or when the instruction
+    /// was processed with `Mollusk::process_instruction_raw`.
     #[cfg(feature = "inner-instructions")]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub message: Option<SanitizedMessage>,
+    /// The full execution timings, of which `execution_time` is the execute
+    /// time.
+    ///
+    /// For an instruction chain, these are the timings of the last processed
+    /// step. This is `None` when the result wasn't produced by the harness,
+    /// such as when loaded from a fixture.
+    #[cfg(feature = "detailed-timings")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub timings: Option<DetailedTimings>,
 }
 
 impl Default for InstructionResult {
     fn default() -> Self {
         Self {
             compute_units_consumed: 0,
+            builtin_compute_units: 0,
+            bpf_compute_units: 0,
             execution_time: 0,
             program_result: ProgramResult::Success,
             raw_result: Ok(()),
@@ -96,6 +169,8 @@ impl Default for InstructionResult {
             inner_instructions: vec![],
             #[cfg(feature = "inner-instructions")]
             message: None,
+            #[cfg(feature = "detailed-timings")]
+            timings: None,
         }
     }
 }
@@ -109,8 +184,78 @@ impl InstructionResult {
             .map(|(_, a)| a)
     }
 
+    /// Get the signed change in lamports of a resulting account, relative to
+    /// the provided lamports balance from before execution.
+    ///
+    /// Returns `None` if the account is not in the resulting accounts.
+    pub fn lamports_delta(&self, pubkey: &Pubkey, before: u64) -> Option<i128> {
+        self.get_account(pubkey)
+            .map(|account| i128::from(account.lamports()) - i128::from(before))
+    }
+
+    /// Whether a resulting account differs from the provided account from
+    /// before execution, in any of its lamports, data, owner, executable flag,
+    /// or rent epoch.
+    ///
+    /// Returns `false` if the account is not in the resulting accounts.
+    pub fn account_changed<A: ReadableAccount>(&self, pubkey: &Pubkey, before: &A) -> bool {
+        self.get_account(pubkey)
+            .is_some_and(|account| !solana_account::accounts_equal(account, before))
+    }
+
+    /// Format the result as a human-readable, multi-line string.
+    ///
+    /// Unlike the `Debug` output, account data and return data are rendered
+    /// as hex, with account data truncated to a short preview.
+    pub fn pretty_print(&self) -> String {
+        const DATA_PREVIEW_LEN: usize = 16;
+
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+
+        let mut out = format!(
+            "compute_units_consumed: {}\nexecution_time: {}\nprogram_result: {:?}\nreturn_data: \
+             0x{}\nresulting_accounts:\n",
+            self.compute_units_consumed,
+            self.execution_time,
+            self.program_result,
+            hex(&self.return_data),
+        );
+        for (pubkey, account) in &self.resulting_accounts {
+            let data = account.data();
+            let preview_len = data.len().min(DATA_PREVIEW_LEN);
+            let ellipsis = if data.len() > DATA_PREVIEW_LEN {
+                "..."
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "  {pubkey}: lamports: {}, owner: {}, space: {}, data: 0x{}{ellipsis}\n",
+                account.lamports(),
+                account.owner(),
+                data.len(),
+                hex(&data[..preview_len]),
+            ));
+        }
+        out
+    }
+
+    /// Absorb the result of a subsequent instruction, such as the next step
+    /// of an instruction chain.
+    ///
+    /// Compute units (including the builtin and BPF breakdown) and execution
+    /// time are accumulated, while everything
+    /// else is replaced by `other`. Notably, return data is replaced even when
+    /// `other` has none, matching the runtime's clearing of return data
+    /// between instructions.
     pub fn absorb(&mut self, other: Self) {
         self.compute_units_consumed += other.compute_units_consumed;
+        self.builtin_compute_units += other.builtin_compute_units;
+        self.bpf_compute_units += other.bpf_compute_units;
         self.execution_time += other.execution_time;
         self.program_result = other.program_result;
         self.raw_result = other.raw_result;
@@ -121,6 +266,109 @@ impl InstructionResult {
             self.inner_instructions = other.inner_instructions;
             self.message = other.message;
         }
+        #[cfg(feature = "detailed-timings")]
+        {
+            self.timings = other.timings;
+        }
+    }
+}
+
+/// The result of an instruction chain, retaining the result of every step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainResult {
+    /// The result of each processed step, in order.
+    ///
+    /// If a step fails, processing stops, so later steps are omitted.
+    pub steps: Vec<InstructionResult>,
+    /// The composite result of the entire chain, as returned by the
+    /// non-retaining chain methods.
+    pub composite: InstructionResult,
+}
+
+impl ChainResult {
+    /// Get the result of the step at the provided index, so additional checks
+    /// can be run against it without re-executing the chain.
+    ///
+    /// Panics if the step was never processed.
+    pub fn step(&self, index: usize) -> &InstructionResult {
+        &self.steps[index]
+    }
+
+    /// Get the compute units consumed by each processed step, in order.
+    ///
+    /// These sum to the composite result's `compute_units_consumed`.
+    pub fn step_compute_units(&self) -> Vec<u64> {
+        self.steps
+            .iter()
+            .map(|step| step.compute_units_consumed)
+            .collect()
+    }
+}
+
+/// The result of an instruction processed by a `MolluskContext`.
+///
+/// This is identical to `InstructionResult`, but omits the
+/// `resulting_accounts`, since accounts are managed by the context's account
+/// store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextResult {
+    /// The number of compute units consumed by the instruction.
+    pub compute_units_consumed: u64,
+    /// The portion of `compute_units_consumed` spent in instructions whose
+    /// top-level program is a builtin (or precompile).
+    pub builtin_compute_units: u64,
+    /// The portion of `compute_units_consumed` spent in instructions whose
+    /// top-level program is a BPF program, including any CPIs it makes.
+    pub bpf_compute_units: u64,
+    /// The time taken to execute the instruction.
+    pub execution_time: u64,
+    /// The result code of the program's execution.
+    pub program_result: ProgramResult,
+    /// The raw result of the program's execution.
+    pub raw_result: Result<(), InstructionError>,
+    /// The return data produced by the instruction, if any.
+    pub return_data: Vec<u8>,
+    /// Inner instructions (CPIs) invoked during the instruction execution.
+    #[cfg(feature = "inner-instructions")]
+    pub inner_instructions: Vec<InnerInstruction>,
+    /// The compiled message used to execute the instruction.
+    #[cfg(feature = "inner-instructions")]
+    pub message: Option<SanitizedMessage>,
+    /// The full execution timings, of which `execution_time` is the execute
+    /// time.
+    #[cfg(feature = "detailed-timings")]
+    pub timings: Option<DetailedTimings>,
+}
+
+impl ContextResult {
+    /// Returns `true` if the program succeeded.
+    pub const fn is_ok(&self) -> bool {
+        self.program_result.is_ok()
+    }
+
+    /// Returns `true` if the program returned an error.
+    pub const fn is_err(&self) -> bool {
+        self.program_result.is_err()
+    }
+}
+
+impl From<InstructionResult> for ContextResult {
+    fn from(result: InstructionResult) -> Self {
+        Self {
+            compute_units_consumed: result.compute_units_consumed,
+            builtin_compute_units: result.builtin_compute_units,
+            bpf_compute_units: result.bpf_compute_units,
+            execution_time: result.execution_time,
+            program_result: result.program_result,
+            raw_result: result.raw_result,
+            return_data: result.return_data,
+            #[cfg(feature = "inner-instructions")]
+            inner_instructions: result.inner_instructions,
+            #[cfg(feature = "inner-instructions")]
+            message: result.message,
+            #[cfg(feature = "detailed-timings")]
+            timings: result.timings,
+        }
     }
 }
 
@@ -183,3 +431,107 @@ pub struct TransactionResult {
     #[cfg(feature = "inner-instructions")]
     pub message: Option<SanitizedMessage>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_changed() {
+        let changed = Pubkey::new_from_array([1; 32]);
+        let unchanged = Pubkey::new_from_array([2; 32]);
+        let absent = Pubkey::new_from_array([3; 32]);
+        let owner = Pubkey::new_from_array([4; 32]);
+
+        let before = solana_account::Account::new(42, 8, &owner);
+
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (changed, AccountSharedData::new(42, 8, &Pubkey::default())),
+                (unchanged, AccountSharedData::from(before.clone())),
+            ],
+            ..Default::default()
+        };
+
+        assert!(result.account_changed(&changed, &before));
+        assert!(!result.account_changed(&unchanged, &before));
+        assert!(!result.account_changed(&absent, &before));
+    }
+
+    #[test]
+    fn test_lamports_delta() {
+        let gained = Pubkey::new_from_array([1; 32]);
+        let lost = Pubkey::new_from_array([2; 32]);
+        let absent = Pubkey::new_from_array([3; 32]);
+
+        let result = InstructionResult {
+            resulting_accounts: vec![
+                (
+                    gained,
+                    AccountSharedData::new(u64::MAX, 0, &Pubkey::default()),
+                ),
+                (lost, AccountSharedData::new(0, 0, &Pubkey::default())),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            result.lamports_delta(&gained, 0),
+            Some(i128::from(u64::MAX))
+        );
+        assert_eq!(result.lamports_delta(&gained, u64::MAX), Some(0));
+        assert_eq!(
+            result.lamports_delta(&lost, u64::MAX),
+            Some(-i128::from(u64::MAX))
+        );
+        assert_eq!(result.lamports_delta(&absent, 42), None);
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let sender = Pubkey::new_from_array([1; 32]);
+        let recipient = Pubkey::new_from_array([2; 32]);
+        let owner = Pubkey::default();
+
+        let result = InstructionResult {
+            compute_units_consumed: 150,
+            return_data: vec![0xde, 0xad],
+            resulting_accounts: vec![
+                (sender, AccountSharedData::new(9_000, 0, &owner)),
+                (recipient, AccountSharedData::new(1_000, 32, &owner)),
+            ],
+            ..Default::default()
+        };
+
+        let output = result.pretty_print();
+        assert!(output.contains("compute_units_consumed: 150"));
+        assert!(output.contains("program_result: Success"));
+        assert!(output.contains("return_data: 0xdead"));
+        assert!(output.contains(&format!(
+            "{sender}: lamports: 9000, owner: {owner}, space: 0, data: 0x\n"
+        )));
+        assert!(output.contains(&format!(
+            "{recipient}: lamports: 1000, owner: {owner}, space: 32, data: 0x{}...\n",
+            "00".repeat(16)
+        )));
+    }
+
+    #[test]
+    fn test_absorb_clears_return_data() {
+        // Step 0 sets return data.
+        let mut composite = InstructionResult {
+            compute_units_consumed: 100,
+            return_data: vec![1, 2, 3],
+            ..Default::default()
+        };
+
+        // Step 1 doesn't.
+        composite.absorb(InstructionResult {
+            compute_units_consumed: 50,
+            ..Default::default()
+        });
+
+        assert_eq!(composite.compute_units_consumed, 150);
+        assert!(composite.return_data.is_empty());
+    }
+}